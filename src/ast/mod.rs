@@ -1,25 +1,35 @@
+pub use crate::span::{ Span, Spanned };
+
+pub mod printer;
+pub mod visit;
+
 #[derive(Debug)]
 pub struct Program {
     pub import_decls: Vec<ImportDecl>,
     pub field_decls: Vec<FieldDecl>,
     pub method_decls: Vec<MethodDecl>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct ImportDecl {
     pub id: Id,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct FieldDecl {
     pub type_: Type,
+    // empty if the declaration failed to parse
     pub field_ids: Vec<FieldDeclId>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct FieldDeclId {
     pub id: Id,
-    pub arr_len: Option<i32>,
+    pub arr_len: Option<IntLiteral>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
@@ -28,6 +38,7 @@ pub struct MethodDecl {
     pub id: Id,
     pub args: Vec<MethodArg>,
     pub block: Block,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -40,12 +51,14 @@ pub enum ReturnType {
 pub struct MethodArg {
     pub type_: Type,
     pub id: Id,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct Block {
     pub field_decls: Vec<FieldDecl>,
     pub statements: Vec<Statement>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -54,8 +67,10 @@ pub enum Type {
     Bool,
 }
 
+pub type Statement = Spanned<Statement_>;
+
 #[derive(Debug)]
-pub enum Statement {
+pub enum Statement_ {
     Assign(Assign),
     MethodCall(MethodCall),
     IfElse(IfElse),
@@ -64,6 +79,8 @@ pub enum Statement {
     Return(Return),
     Break,
     Continue,
+    // placeholder for a statement that failed to parse
+    Error,
 }
 
 #[derive(Debug)]
@@ -103,6 +120,7 @@ pub struct Return {
 pub struct ForUpdate {
     pub location: Location,
     pub update_expr: ForUpdateExpr,
+    pub span: Span,
 }
 
 #[derive(Debug)]
@@ -157,12 +175,14 @@ pub enum MethodCall {
 pub struct MethodCall0 {
     pub name: MethodName,
     pub args: Vec<Expr>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct MethodCall1 {
     pub name: MethodName,
     pub args: Vec<ImportArg>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -176,13 +196,39 @@ pub enum Location {
     IdExpr(IdExpr),
 }
 
+impl MethodCall {
+    pub fn span(&self) -> Span {
+        match self {
+            MethodCall::Method(m) => m.span,
+            MethodCall::Callout(c) => c.span,
+        }
+    }
+}
+
+impl Location {
+    pub fn id(&self) -> &Id {
+        match self {
+            Location::Id(id) => id,
+            Location::IdExpr(e) => &e.id,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Location::Id(id) => id.span,
+            Location::IdExpr(e) => e.span,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct IdExpr {
     pub id: Id,
     pub array_len: Expr,
+    pub span: Span,
 }
 
-pub type Expr = Box<Expr_>;
+pub type Expr = Box<Spanned<Expr_>>;
 
 #[derive(Debug, Clone)]
 pub enum Expr_ {
@@ -216,7 +262,7 @@ pub struct Binary {
 #[derive(Debug, Clone)]
 pub enum ImportArg {
     Expr(Expr),
-    StringLiteral(StringLiteral),
+    StringLiteral(Spanned<StringLiteral>),
 }
 
 #[derive(Debug, Clone)]
@@ -263,9 +309,31 @@ pub enum Literal {
     BoolLiteral(BoolLiteral),
 }
 
-pub type IntLiteral = i32;
-pub type DecimalLiteral = i32;
-pub type HexLiteral = i32;
+// kept as written so that range checking can take a preceding `-` into account
+#[derive(Debug, Clone)]
+pub enum IntLiteral {
+    Decimal(DecimalLiteral),
+    Hex(HexLiteral),
+}
+
+impl IntLiteral {
+    pub fn text(&self) -> &str {
+        match self {
+            IntLiteral::Decimal(s) | IntLiteral::Hex(s) => s,
+        }
+    }
+
+    // `None` if the value does not even fit in an i64
+    pub fn value(&self) -> Option<i64> {
+        match self {
+            IntLiteral::Decimal(s) => s.parse().ok(),
+            IntLiteral::Hex(s) => i64::from_str_radix(&s[2..], 16).ok(),
+        }
+    }
+}
+
+pub type DecimalLiteral = String;
+pub type HexLiteral = String;
 
 #[derive(Debug, Clone)]
 pub enum BoolLiteral {
@@ -275,4 +343,4 @@ pub enum BoolLiteral {
 pub type CharLiteral = char;
 pub type StringLiteral = String;
 
-pub type Id = String;
+pub type Id = Spanned<String>;
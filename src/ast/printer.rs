@@ -0,0 +1,623 @@
+use super::*;
+use crate::scanner::{ escape_char, escape_string, Comment, Scanner, SpannedToken, Token };
+
+#[derive(Debug, Clone)]
+pub struct PrintOptions {
+    /// Number of spaces per indentation level.
+    pub indent: usize,
+    /// Split `int a, b;` into `int a;` and `int b;`.
+    pub one_decl_per_line: bool,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self { indent: 4, one_decl_per_line: false }
+    }
+}
+
+/// Turns a program back into Decaf source.
+///
+/// The output re-parses into the same AST (up to spans): parentheses are
+/// inserted exactly where precedence and associativity require them.
+pub fn print_program(program: &Program, options: &PrintOptions) -> String {
+    format_program(program, "", &[], options)
+}
+
+/// Like `print_program`, but puts back the comments of `src`, the text
+/// `program` was parsed from, and keeps single blank lines between items.
+///
+/// Comments go before the item they precede; a comment sharing a source line
+/// with the end of an item stays at the end of that item's line. Block
+/// comments inside a declaration, statement or header stay before the token
+/// they precede, while line comments there go to the end of the line.
+pub fn format_program(program: &Program, src: &str, comments: &[Comment], options: &PrintOptions) -> String {
+    let mut printer = Printer {
+        options,
+        src,
+        comments,
+        next_comment: 0,
+        last_end: 0,
+        blank_line: false,
+        trailing: None,
+        line_comment: false,
+        out: String::new(),
+        level: 0,
+    };
+    printer.program(program);
+    printer.comments_before(usize::MAX);
+    printer.out
+}
+
+/// Prints a single expression with the minimal parentheses.
+pub fn print_expr(expr: &Expr) -> String {
+    expr_prec(expr, 0)
+}
+
+struct Printer<'a> {
+    options: &'a PrintOptions,
+    src: &'a str,
+    comments: &'a [Comment],
+    next_comment: usize,
+    // source offset just past the last printed item
+    last_end: usize,
+    // whether the next line should be preceded by a blank one
+    blank_line: bool,
+    // a line comment from inside the next line, which goes at its end
+    trailing: Option<&'a str>,
+    // whether the last line printed ends with a line comment, after which
+    // nothing can go on that line
+    line_comment: bool,
+    out: String,
+    level: usize,
+}
+
+impl<'a> Printer<'a> {
+    fn line(&mut self, s: &str) {
+        // never open a block or the file with a blank line
+        if self.blank_line && !self.out.is_empty() && !self.out.ends_with("{\n") {
+            self.out.push('\n');
+        }
+        self.blank_line = false;
+        self.out.push_str(&" ".repeat(self.level * self.options.indent));
+        self.out.push_str(s);
+        self.line_comment = s.starts_with("//");
+        if let Some(comment) = self.trailing.take() {
+            self.out.push(' ');
+            self.out.push_str(comment);
+            self.line_comment = true;
+        }
+        self.out.push('\n');
+    }
+
+    fn has_blank_line(&self, start: usize, end: usize) -> bool {
+        self.src.get(start..end).is_some_and(|s| s.matches('\n').count() >= 2)
+    }
+
+    fn comments_before(&mut self, pos: usize) {
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.span.start >= pos {
+                break;
+            }
+            let text = &self.src[comment.span.start..comment.span.end];
+            let same_line = comment.span.start < self.last_end || !self.src[self.last_end..comment.span.start].contains('\n');
+            if same_line && !self.out.is_empty() && !self.line_comment {
+                self.out.pop();
+                self.out.push(' ');
+                self.out.push_str(text);
+                self.out.push('\n');
+                self.line_comment = !comment.block;
+            } else {
+                self.blank_line |= self.has_blank_line(self.last_end, comment.span.start);
+                self.line(text);
+            }
+            self.last_end = self.last_end.max(comment.span.end);
+            self.next_comment += 1;
+        }
+    }
+
+    // Puts the comments inside the source range `start..end`, which was
+    // printed as `text`, back into it. A block comment goes before the token
+    // it precedes; the tokens of `text` are matched with those of the source
+    // up to the grouping parentheses the printer drops.
+    fn inline(&mut self, text: String, start: usize, end: usize) -> String {
+        let mut inserts = Vec::new();
+        let mut tokens = None;
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.span.start >= end {
+                break;
+            }
+            self.next_comment += 1;
+            self.last_end = self.last_end.max(comment.span.end);
+            let comment_text = &self.src[comment.span.start..comment.span.end];
+            if !comment.block {
+                // only one line comment fits at the end of the line
+                if let Some(pending) = self.trailing.take() {
+                    self.line(pending);
+                }
+                self.trailing = Some(comment_text);
+                continue;
+            }
+            let (source, matched) = tokens.get_or_insert_with(|| {
+                let source: Vec<SpannedToken> = Scanner::new(&self.src[start..end]).filter_map(Result::ok).collect();
+                let printed: Vec<SpannedToken> = Scanner::new(&text).filter_map(Result::ok).collect();
+                let matched = match_tokens(&source, &printed);
+                (source, matched)
+            });
+            // before the first source token after the comment that was printed
+            let pos = source
+                .iter()
+                .zip(matched.iter())
+                .find_map(|((token_start, _, _), pos)| pos.filter(|_| start + token_start >= comment.span.end))
+                .unwrap_or(text.len());
+            inserts.push((pos, comment_text));
+        }
+        if inserts.is_empty() {
+            return text;
+        }
+
+        let mut out = String::new();
+        let mut copied = 0;
+        for (pos, comment) in inserts {
+            out.push_str(&text[copied..pos]);
+            copied = pos;
+            if !out.is_empty() && !out.ends_with([' ', '(', '[']) {
+                out.push(' ');
+            }
+            out.push_str(comment);
+            if text[pos..].starts_with(|c: char| !matches!(c, ' ' | ')' | ']' | ',' | ';')) {
+                out.push(' ');
+            }
+        }
+        out.push_str(&text[copied..]);
+        out
+    }
+
+    // called before printing an item that starts at source offset `start`
+    fn item(&mut self, start: usize) {
+        self.comments_before(start);
+        self.blank_line |= self.has_blank_line(self.last_end, start);
+    }
+
+    fn program(&mut self, program: &Program) {
+        for import in &program.import_decls {
+            self.item(import.span.start);
+            self.line(&format!("import {};", import.id.node));
+            self.last_end = import.span.end;
+        }
+        self.blank_line = !program.import_decls.is_empty();
+        for field in &program.field_decls {
+            self.field_decl(field);
+        }
+        for method in &program.method_decls {
+            self.blank_line = true;
+            self.method_decl(method);
+        }
+    }
+
+    fn field_decl(&mut self, field: &FieldDecl) {
+        self.item(field.span.start);
+        let ids: Vec<String> = field.field_ids.iter().map(|f| match &f.arr_len {
+            Some(len) => format!("{}[{}]", f.id.node, len.text()),
+            None => f.id.node.clone(),
+        }).collect();
+        if self.options.one_decl_per_line {
+            for id in ids {
+                self.line(&format!("{} {};", type_name(&field.type_), id));
+            }
+        } else {
+            let s = format!("{} {};", type_name(&field.type_), ids.join(", "));
+            let s = self.inline(s, field.span.start, field.span.end);
+            self.line(&s);
+        }
+        self.last_end = field.span.end;
+    }
+
+    fn method_decl(&mut self, method: &MethodDecl) {
+        self.item(method.span.start);
+        let return_type = match &method.return_type {
+            ReturnType::Type(t) => type_name(t),
+            ReturnType::Void => "void",
+        };
+        let args: Vec<String> = method.args.iter().map(|a| format!("{} {}", type_name(&a.type_), a.id.node)).collect();
+        let header = format!("{} {}({})", return_type, method.id.node, args.join(", "));
+        let header = self.inline(header, method.span.start, method.block.span.start);
+        self.block(&header, &method.block);
+    }
+
+    // prints `<header> {`, the block's contents and the closing brace
+    fn block(&mut self, header: &str, block: &Block) {
+        self.line(&format!("{} {{", header));
+        self.block_body(block);
+        self.line("}");
+        self.last_end = block.span.end;
+    }
+
+    fn block_body(&mut self, block: &Block) {
+        self.last_end = block.span.start + 1;
+        self.level += 1;
+        for field in &block.field_decls {
+            self.field_decl(field);
+        }
+        for statement in &block.statements {
+            self.statement(statement);
+        }
+        // comments before the closing brace stay inside the block
+        self.comments_before(block.span.end - 1);
+        self.level -= 1;
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        self.item(statement.span.start);
+        let (start, end) = (statement.span.start, statement.span.end);
+        match &statement.node {
+            Statement_::Assign(a) => {
+                let s = format!("{}{};", location(&a.dst), assign_expr(&a.assign_expr));
+                let s = self.inline(s, start, end);
+                self.line(&s);
+            }
+            Statement_::MethodCall(m) => {
+                let s = self.inline(format!("{};", method_call(m)), start, end);
+                self.line(&s);
+            }
+            Statement_::IfElse(i) => {
+                let header = format!("if ({})", print_expr(&i.cond));
+                let header = self.inline(header, start, i.if_block.span.start);
+                match &i.else_block {
+                    None => self.block(&header, &i.if_block),
+                    Some(e) => {
+                        self.line(&format!("{} {{", header));
+                        self.block_body(&i.if_block);
+                        self.line("} else {");
+                        self.block_body(e);
+                        self.line("}");
+                    }
+                }
+            }
+            Statement_::Loop(l) => {
+                let header = format!(
+                    "for ({} = {}; {}; {})",
+                    l.id.node,
+                    print_expr(&l.init_expr),
+                    print_expr(&l.incre_expr),
+                    for_update(&l.update),
+                );
+                let header = self.inline(header, start, l.block.span.start);
+                self.block(&header, &l.block);
+            }
+            Statement_::While(w) => {
+                let header = self.inline(format!("while ({})", print_expr(&w.expr)), start, w.block.span.start);
+                self.block(&header, &w.block);
+            }
+            Statement_::Return(r) => {
+                let s = match &r.expr {
+                    Some(e) => format!("return {};", print_expr(e)),
+                    None => "return;".to_string(),
+                };
+                let s = self.inline(s, start, end);
+                self.line(&s);
+            }
+            Statement_::Break => {
+                let s = self.inline("break;".to_string(), start, end);
+                self.line(&s);
+            }
+            Statement_::Continue => {
+                let s = self.inline("continue;".to_string(), start, end);
+                self.line(&s);
+            }
+            Statement_::Error => self.line("/* syntax error */"),
+        }
+        self.last_end = statement.span.end;
+    }
+}
+
+// Matches the tokens the printer wrote for a piece of source with the tokens
+// of the source itself, giving the offset in the printed text of each
+// source token, or `None` for grouping parentheses the printer left out.
+fn match_tokens(source: &[SpannedToken], printed: &[SpannedToken]) -> Vec<Option<usize>> {
+    let is_paren = |t: &Token| matches!(t, Token::LParen | Token::RParen);
+    let mut matched = Vec::new();
+    let mut printed = printed.iter().peekable();
+    for (_, token, _) in source {
+        while printed.peek().is_some_and(|(_, p, _)| p != token && is_paren(p)) {
+            printed.next();
+        }
+        match printed.peek() {
+            Some((_, p, _)) if p != token && is_paren(token) => matched.push(None),
+            Some((pos, _, _)) => {
+                matched.push(Some(*pos));
+                printed.next();
+            }
+            None => matched.push(None),
+        }
+    }
+    matched
+}
+
+fn type_name(t: &Type) -> &'static str {
+    match t {
+        Type::Int => "int",
+        Type::Bool => "bool",
+    }
+}
+
+fn location(l: &Location) -> String {
+    match l {
+        Location::Id(id) => id.node.clone(),
+        Location::IdExpr(e) => format!("{}[{}]", e.id.node, print_expr(&e.array_len)),
+    }
+}
+
+fn compound_assign_op(op: &CompoundAssignOp) -> &'static str {
+    match op {
+        CompoundAssignOp::AddAssign => "+=",
+        CompoundAssignOp::SubAssign => "-=",
+    }
+}
+
+fn increment(i: &Increment) -> &'static str {
+    match i {
+        Increment::SelfAdd => "++",
+        Increment::SelfSub => "--",
+    }
+}
+
+fn assign_expr(a: &AssignExpr) -> String {
+    match a {
+        AssignExpr::AssignOpExpr(e) => {
+            let op = match &e.assign_op {
+                AssignOp::Assign => "=",
+                AssignOp::CompoundAssignOp(c) => compound_assign_op(c),
+            };
+            format!(" {} {}", op, print_expr(&e.expr))
+        }
+        AssignExpr::Increment(i) => increment(i).to_string(),
+    }
+}
+
+fn for_update(u: &ForUpdate) -> String {
+    match &u.update_expr {
+        ForUpdateExpr::AssignExpr(e) => format!(
+            "{} {} {}",
+            location(&u.location),
+            compound_assign_op(&e.compound_assign_op),
+            print_expr(&e.expr),
+        ),
+        ForUpdateExpr::Increment(i) => format!("{}{}", location(&u.location), increment(i)),
+    }
+}
+
+fn method_call(m: &MethodCall) -> String {
+    match m {
+        MethodCall::Method(m) => {
+            let args: Vec<String> = m.args.iter().map(print_expr).collect();
+            format!("{}({})", m.name.id.node, args.join(", "))
+        }
+        MethodCall::Callout(c) => {
+            let mut args = vec![format!("\"{}\"", escape_string(&c.name.id.node))];
+            args.extend(c.args.iter().map(|a| match a {
+                ImportArg::Expr(e) => print_expr(e),
+                ImportArg::StringLiteral(s) => format!("\"{}\"", escape_string(&s.node)),
+            }));
+            format!("callout({})", args.join(", "))
+        }
+    }
+}
+
+pub fn binary_op(op: &BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::ArithOp(ArithOp::Add) => "+",
+        BinaryOp::ArithOp(ArithOp::Sub) => "-",
+        BinaryOp::ArithOp(ArithOp::Mul) => "*",
+        BinaryOp::ArithOp(ArithOp::Div) => "/",
+        BinaryOp::ArithOp(ArithOp::Mod) => "%",
+        BinaryOp::RelOp(RelOp::Less) => "<",
+        BinaryOp::RelOp(RelOp::LessEq) => "<=",
+        BinaryOp::RelOp(RelOp::Greater) => ">",
+        BinaryOp::RelOp(RelOp::GreaterEq) => ">=",
+        BinaryOp::EqOp(EqOp::EQ) => "==",
+        BinaryOp::EqOp(EqOp::NE) => "!=",
+        BinaryOp::CondOp(CondOp::And) => "&&",
+        BinaryOp::CondOp(CondOp::Or) => "||",
+    }
+}
+
+// binding strength of each level of the expression grammar, loosest first
+const PREC_OR: u8 = 1;
+const PREC_AND: u8 = 2;
+const PREC_EQ: u8 = 3;
+const PREC_REL: u8 = 4;
+const PREC_ADD: u8 = 5;
+const PREC_MUL: u8 = 6;
+const PREC_NOT: u8 = 7;
+const PREC_ATOM: u8 = 8;
+
+fn binary_prec(op: &BinaryOp) -> u8 {
+    match op {
+        BinaryOp::CondOp(CondOp::Or) => PREC_OR,
+        BinaryOp::CondOp(CondOp::And) => PREC_AND,
+        BinaryOp::EqOp(_) => PREC_EQ,
+        BinaryOp::RelOp(_) => PREC_REL,
+        BinaryOp::ArithOp(ArithOp::Add | ArithOp::Sub) => PREC_ADD,
+        BinaryOp::ArithOp(_) => PREC_MUL,
+    }
+}
+
+fn expr_prec_of(expr: &Expr) -> u8 {
+    match &expr.node {
+        Expr_::Binary(b) => binary_prec(&b.op),
+        Expr_::Unary(Unary { op: UnaryOp::NegBool, .. }) => PREC_NOT,
+        // unary minus sits at the same level as the atoms it applies to
+        _ => PREC_ATOM,
+    }
+}
+
+// prints `expr` in a context that needs at least precedence `min`
+fn expr_prec(expr: &Expr, min: u8) -> String {
+    let prec = expr_prec_of(expr);
+    let s = match &expr.node {
+        Expr_::Location(l) => location(l),
+        Expr_::MethodCall(m) => method_call(m),
+        Expr_::Literal(l) => literal(l),
+        Expr_::LenId(id) => format!("len({})", id.node),
+        Expr_::Unary(u) => match u.op {
+            UnaryOp::NegBool => format!("!{}", expr_prec(&u.expr, PREC_NOT)),
+            UnaryOp::NegInt => {
+                let operand = expr_prec(&u.expr, PREC_ATOM);
+                // `--` would scan as a decrement
+                if operand.starts_with('-') {
+                    format!("-({})", operand)
+                } else {
+                    format!("-{}", operand)
+                }
+            }
+        },
+        // all binary operators are left-associative
+        Expr_::Binary(b) => format!(
+            "{} {} {}",
+            expr_prec(&b.lhs, prec),
+            binary_op(&b.op),
+            expr_prec(&b.rhs, prec + 1),
+        ),
+    };
+    if prec < min {
+        format!("({})", s)
+    } else {
+        s
+    }
+}
+
+fn literal(l: &Literal) -> String {
+    match l {
+        Literal::IntLiteral(i) => i.text().to_string(),
+        Literal::CharLiteral(c) => format!("'{}'", escape_char(*c)),
+        Literal::BoolLiteral(BoolLiteral::True) => "true".to_string(),
+        Literal::BoolLiteral(BoolLiteral::False) => "false".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{ read_dir, read_to_string };
+    use std::path::PathBuf;
+    use regex::Regex;
+    use super::*;
+    use crate::parser::parse;
+    use crate::scanner::scan_comments;
+    use crate::test_util::get_current_dir;
+
+    // Debug output of a program with every span removed
+    fn shape(program: &Program) -> String {
+        let span = Regex::new(r"span: Span \{ start: \d+, end: \d+ \}").unwrap();
+        span.replace_all(&format!("{:?}", program), "").into_owned()
+    }
+
+    fn round_trip(code: &str, options: &PrintOptions) -> String {
+        let program = parse(code).unwrap();
+        let printed = print_program(&program, options);
+        let reparsed = parse(&printed).unwrap_or_else(|_| panic!("printed program does not parse:\n{}", printed));
+        assert_eq!(shape(&program), shape(&reparsed), "printed program differs:\n{}", printed);
+        printed
+    }
+
+    #[test]
+    fn test_round_trip_legal_testcases() {
+        let dir: PathBuf = [&get_current_dir(), "src", "parser", "testcases", "legal"].iter().collect();
+        let mut count = 0;
+        for entry in read_dir(dir).unwrap() {
+            let code = read_to_string(entry.unwrap().path()).unwrap();
+            let printed = round_trip(&code, &PrintOptions::default());
+            // printing is idempotent
+            assert_eq!(print_program(&parse(&printed).unwrap(), &PrintOptions::default()), printed);
+            count += 1;
+        }
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn test_parentheses() {
+        let code = "void main() {
+            x = (a + b) * c;
+            x = a - (b - c);
+            x = (a - b) - c;
+            x = !(a && b) || c;
+            x = -(a + 1);
+            x = - -a;
+            x = -(-1);
+            x = !-a;
+            x = (a < b) == (c >= d);
+            x = a < (b < c);
+        }";
+        let printed = round_trip(code, &PrintOptions::default());
+        let lines: Vec<&str> = printed.lines().skip(1).map(|l| l.trim()).collect();
+        assert_eq!(lines, vec![
+            "x = (a + b) * c;",
+            "x = a - (b - c);",
+            "x = a - b - c;",
+            "x = !(a && b) || c;",
+            "x = -(a + 1);",
+            "x = -(-a);",
+            "x = -(-1);",
+            "x = !-a;",
+            "x = a < b == c >= d;",
+            "x = a < (b < c);",
+            "}",
+        ]);
+    }
+
+    #[test]
+    fn test_layout() {
+        let code = "import printf; int a, b[0x10]; bool c;
+            int foo(int x, bool y) { int z; if (y) { z = x; } else { z = -x; } return z; }
+            void main() { for (i = 0; i < 10; i += 2) { while (true) { break; } } callout(\"putchar\", '\\n', \"a\\\"b\"); a++; }";
+        let printed = round_trip(code, &PrintOptions { indent: 2, ..PrintOptions::default() });
+        let expected = "\
+import printf;
+
+int a, b[0x10];
+bool c;
+
+int foo(int x, bool y) {
+  int z;
+  if (y) {
+    z = x;
+  } else {
+    z = -x;
+  }
+  return z;
+}
+
+void main() {
+  for (i = 0; i < 10; i += 2) {
+    while (true) {
+      break;
+    }
+  }
+  callout(\"putchar\", '\\n', \"a\\\"b\");
+  a++;
+}
+";
+        assert_eq!(printed, expected);
+    }
+
+    #[test]
+    fn test_line_comments_keep_their_own_line() {
+        let code = "void main() {\n    if (true) {\n        a = 1;\n    } // after if\n    else { // in else\n        a = b // one\n          + c // two\n          ;\n    }\n}\n";
+        let expected = "\
+void main() {
+    if (true) {
+        a = 1;
+    } else { // after if
+        // in else
+        // one
+        a = b + c; // two
+    }
+}
+";
+        let options = PrintOptions::default();
+        let printed = format_program(&parse(code).unwrap(), code, &scan_comments(code), &options);
+        assert_eq!(printed, expected);
+        let comments = scan_comments(&printed);
+        assert_eq!(comments.len(), 4);
+        assert_eq!(format_program(&parse(&printed).unwrap(), &printed, &comments, &options), printed);
+    }
+}
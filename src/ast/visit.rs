@@ -0,0 +1,528 @@
+//! Traversal of the AST.
+//!
+//! Each `visit_*` method defaults to the matching `walk_*` function, which
+//! visits the children of the node in source order. Implementors override
+//! the methods for the nodes they care about and call `walk_*` from them to
+//! keep descending.
+
+use super::*;
+
+pub trait Visitor {
+    fn visit_program(&mut self, program: &Program) {
+        walk_program(self, program)
+    }
+
+    fn visit_import_decl(&mut self, import: &ImportDecl) {
+        walk_import_decl(self, import)
+    }
+
+    fn visit_field_decl(&mut self, field: &FieldDecl) {
+        walk_field_decl(self, field)
+    }
+
+    fn visit_field_decl_id(&mut self, field_id: &FieldDeclId) {
+        walk_field_decl_id(self, field_id)
+    }
+
+    fn visit_method_decl(&mut self, method: &MethodDecl) {
+        walk_method_decl(self, method)
+    }
+
+    fn visit_method_arg(&mut self, arg: &MethodArg) {
+        walk_method_arg(self, arg)
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        walk_block(self, block)
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        walk_statement(self, statement)
+    }
+
+    fn visit_assign(&mut self, assign: &Assign) {
+        walk_assign(self, assign)
+    }
+
+    fn visit_if_else(&mut self, if_else: &IfElse) {
+        walk_if_else(self, if_else)
+    }
+
+    fn visit_loop(&mut self, loop_: &Loop) {
+        walk_loop(self, loop_)
+    }
+
+    fn visit_while(&mut self, while_: &While) {
+        walk_while(self, while_)
+    }
+
+    fn visit_return(&mut self, return_: &Return) {
+        walk_return(self, return_)
+    }
+
+    fn visit_for_update(&mut self, update: &ForUpdate) {
+        walk_for_update(self, update)
+    }
+
+    fn visit_method_call(&mut self, call: &MethodCall) {
+        walk_method_call(self, call)
+    }
+
+    fn visit_import_arg(&mut self, arg: &ImportArg) {
+        walk_import_arg(self, arg)
+    }
+
+    fn visit_location(&mut self, location: &Location) {
+        walk_location(self, location)
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr)
+    }
+
+    fn visit_literal(&mut self, _literal: &Literal) {}
+
+    fn visit_id(&mut self, _id: &Id) {}
+}
+
+pub fn walk_program<V: Visitor + ?Sized>(v: &mut V, program: &Program) {
+    for import in &program.import_decls {
+        v.visit_import_decl(import);
+    }
+    for field in &program.field_decls {
+        v.visit_field_decl(field);
+    }
+    for method in &program.method_decls {
+        v.visit_method_decl(method);
+    }
+}
+
+pub fn walk_import_decl<V: Visitor + ?Sized>(v: &mut V, import: &ImportDecl) {
+    v.visit_id(&import.id);
+}
+
+pub fn walk_field_decl<V: Visitor + ?Sized>(v: &mut V, field: &FieldDecl) {
+    for field_id in &field.field_ids {
+        v.visit_field_decl_id(field_id);
+    }
+}
+
+pub fn walk_field_decl_id<V: Visitor + ?Sized>(v: &mut V, field_id: &FieldDeclId) {
+    v.visit_id(&field_id.id);
+}
+
+pub fn walk_method_decl<V: Visitor + ?Sized>(v: &mut V, method: &MethodDecl) {
+    v.visit_id(&method.id);
+    for arg in &method.args {
+        v.visit_method_arg(arg);
+    }
+    v.visit_block(&method.block);
+}
+
+pub fn walk_method_arg<V: Visitor + ?Sized>(v: &mut V, arg: &MethodArg) {
+    v.visit_id(&arg.id);
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(v: &mut V, block: &Block) {
+    for field in &block.field_decls {
+        v.visit_field_decl(field);
+    }
+    for statement in &block.statements {
+        v.visit_statement(statement);
+    }
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(v: &mut V, statement: &Statement) {
+    match &statement.node {
+        Statement_::Assign(a) => v.visit_assign(a),
+        Statement_::MethodCall(m) => v.visit_method_call(m),
+        Statement_::IfElse(i) => v.visit_if_else(i),
+        Statement_::Loop(l) => v.visit_loop(l),
+        Statement_::While(w) => v.visit_while(w),
+        Statement_::Return(r) => v.visit_return(r),
+        Statement_::Break | Statement_::Continue | Statement_::Error => {}
+    }
+}
+
+pub fn walk_assign<V: Visitor + ?Sized>(v: &mut V, assign: &Assign) {
+    v.visit_location(&assign.dst);
+    if let AssignExpr::AssignOpExpr(e) = &assign.assign_expr {
+        v.visit_expr(&e.expr);
+    }
+}
+
+pub fn walk_if_else<V: Visitor + ?Sized>(v: &mut V, if_else: &IfElse) {
+    v.visit_expr(&if_else.cond);
+    v.visit_block(&if_else.if_block);
+    if let Some(b) = &if_else.else_block {
+        v.visit_block(b);
+    }
+}
+
+pub fn walk_loop<V: Visitor + ?Sized>(v: &mut V, loop_: &Loop) {
+    v.visit_id(&loop_.id);
+    v.visit_expr(&loop_.init_expr);
+    v.visit_expr(&loop_.incre_expr);
+    v.visit_for_update(&loop_.update);
+    v.visit_block(&loop_.block);
+}
+
+pub fn walk_while<V: Visitor + ?Sized>(v: &mut V, while_: &While) {
+    v.visit_expr(&while_.expr);
+    v.visit_block(&while_.block);
+}
+
+pub fn walk_return<V: Visitor + ?Sized>(v: &mut V, return_: &Return) {
+    if let Some(e) = &return_.expr {
+        v.visit_expr(e);
+    }
+}
+
+pub fn walk_for_update<V: Visitor + ?Sized>(v: &mut V, update: &ForUpdate) {
+    v.visit_location(&update.location);
+    if let ForUpdateExpr::AssignExpr(e) = &update.update_expr {
+        v.visit_expr(&e.expr);
+    }
+}
+
+pub fn walk_method_call<V: Visitor + ?Sized>(v: &mut V, call: &MethodCall) {
+    match call {
+        MethodCall::Method(m) => {
+            v.visit_id(&m.name.id);
+            for arg in &m.args {
+                v.visit_expr(arg);
+            }
+        }
+        MethodCall::Callout(c) => {
+            for arg in &c.args {
+                v.visit_import_arg(arg);
+            }
+        }
+    }
+}
+
+pub fn walk_import_arg<V: Visitor + ?Sized>(v: &mut V, arg: &ImportArg) {
+    if let ImportArg::Expr(e) = arg {
+        v.visit_expr(e);
+    }
+}
+
+pub fn walk_location<V: Visitor + ?Sized>(v: &mut V, location: &Location) {
+    match location {
+        Location::Id(id) => v.visit_id(id),
+        Location::IdExpr(e) => {
+            v.visit_id(&e.id);
+            v.visit_expr(&e.array_len);
+        }
+    }
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(v: &mut V, expr: &Expr) {
+    match &expr.node {
+        Expr_::Location(l) => v.visit_location(l),
+        Expr_::MethodCall(m) => v.visit_method_call(m),
+        Expr_::Literal(l) => v.visit_literal(l),
+        Expr_::LenId(id) => v.visit_id(id),
+        Expr_::Unary(u) => v.visit_expr(&u.expr),
+        Expr_::Binary(b) => {
+            v.visit_expr(&b.lhs);
+            v.visit_expr(&b.rhs);
+        }
+    }
+}
+
+/// Like `Visitor`, but with mutable access so that nodes can be rewritten
+/// in place.
+pub trait VisitorMut {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        walk_program_mut(self, program)
+    }
+
+    fn visit_import_decl_mut(&mut self, import: &mut ImportDecl) {
+        walk_import_decl_mut(self, import)
+    }
+
+    fn visit_field_decl_mut(&mut self, field: &mut FieldDecl) {
+        walk_field_decl_mut(self, field)
+    }
+
+    fn visit_field_decl_id_mut(&mut self, field_id: &mut FieldDeclId) {
+        walk_field_decl_id_mut(self, field_id)
+    }
+
+    fn visit_method_decl_mut(&mut self, method: &mut MethodDecl) {
+        walk_method_decl_mut(self, method)
+    }
+
+    fn visit_method_arg_mut(&mut self, arg: &mut MethodArg) {
+        walk_method_arg_mut(self, arg)
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        walk_block_mut(self, block)
+    }
+
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        walk_statement_mut(self, statement)
+    }
+
+    fn visit_assign_mut(&mut self, assign: &mut Assign) {
+        walk_assign_mut(self, assign)
+    }
+
+    fn visit_if_else_mut(&mut self, if_else: &mut IfElse) {
+        walk_if_else_mut(self, if_else)
+    }
+
+    fn visit_loop_mut(&mut self, loop_: &mut Loop) {
+        walk_loop_mut(self, loop_)
+    }
+
+    fn visit_while_mut(&mut self, while_: &mut While) {
+        walk_while_mut(self, while_)
+    }
+
+    fn visit_return_mut(&mut self, return_: &mut Return) {
+        walk_return_mut(self, return_)
+    }
+
+    fn visit_for_update_mut(&mut self, update: &mut ForUpdate) {
+        walk_for_update_mut(self, update)
+    }
+
+    fn visit_method_call_mut(&mut self, call: &mut MethodCall) {
+        walk_method_call_mut(self, call)
+    }
+
+    fn visit_import_arg_mut(&mut self, arg: &mut ImportArg) {
+        walk_import_arg_mut(self, arg)
+    }
+
+    fn visit_location_mut(&mut self, location: &mut Location) {
+        walk_location_mut(self, location)
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr)
+    }
+
+    fn visit_literal_mut(&mut self, _literal: &mut Literal) {}
+
+    fn visit_id_mut(&mut self, _id: &mut Id) {}
+}
+
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(v: &mut V, program: &mut Program) {
+    for import in &mut program.import_decls {
+        v.visit_import_decl_mut(import);
+    }
+    for field in &mut program.field_decls {
+        v.visit_field_decl_mut(field);
+    }
+    for method in &mut program.method_decls {
+        v.visit_method_decl_mut(method);
+    }
+}
+
+pub fn walk_import_decl_mut<V: VisitorMut + ?Sized>(v: &mut V, import: &mut ImportDecl) {
+    v.visit_id_mut(&mut import.id);
+}
+
+pub fn walk_field_decl_mut<V: VisitorMut + ?Sized>(v: &mut V, field: &mut FieldDecl) {
+    for field_id in &mut field.field_ids {
+        v.visit_field_decl_id_mut(field_id);
+    }
+}
+
+pub fn walk_field_decl_id_mut<V: VisitorMut + ?Sized>(v: &mut V, field_id: &mut FieldDeclId) {
+    v.visit_id_mut(&mut field_id.id);
+}
+
+pub fn walk_method_decl_mut<V: VisitorMut + ?Sized>(v: &mut V, method: &mut MethodDecl) {
+    v.visit_id_mut(&mut method.id);
+    for arg in &mut method.args {
+        v.visit_method_arg_mut(arg);
+    }
+    v.visit_block_mut(&mut method.block);
+}
+
+pub fn walk_method_arg_mut<V: VisitorMut + ?Sized>(v: &mut V, arg: &mut MethodArg) {
+    v.visit_id_mut(&mut arg.id);
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(v: &mut V, block: &mut Block) {
+    for field in &mut block.field_decls {
+        v.visit_field_decl_mut(field);
+    }
+    for statement in &mut block.statements {
+        v.visit_statement_mut(statement);
+    }
+}
+
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, statement: &mut Statement) {
+    match &mut statement.node {
+        Statement_::Assign(a) => v.visit_assign_mut(a),
+        Statement_::MethodCall(m) => v.visit_method_call_mut(m),
+        Statement_::IfElse(i) => v.visit_if_else_mut(i),
+        Statement_::Loop(l) => v.visit_loop_mut(l),
+        Statement_::While(w) => v.visit_while_mut(w),
+        Statement_::Return(r) => v.visit_return_mut(r),
+        Statement_::Break | Statement_::Continue | Statement_::Error => {}
+    }
+}
+
+pub fn walk_assign_mut<V: VisitorMut + ?Sized>(v: &mut V, assign: &mut Assign) {
+    v.visit_location_mut(&mut assign.dst);
+    if let AssignExpr::AssignOpExpr(e) = &mut assign.assign_expr {
+        v.visit_expr_mut(&mut e.expr);
+    }
+}
+
+pub fn walk_if_else_mut<V: VisitorMut + ?Sized>(v: &mut V, if_else: &mut IfElse) {
+    v.visit_expr_mut(&mut if_else.cond);
+    v.visit_block_mut(&mut if_else.if_block);
+    if let Some(b) = &mut if_else.else_block {
+        v.visit_block_mut(b);
+    }
+}
+
+pub fn walk_loop_mut<V: VisitorMut + ?Sized>(v: &mut V, loop_: &mut Loop) {
+    v.visit_id_mut(&mut loop_.id);
+    v.visit_expr_mut(&mut loop_.init_expr);
+    v.visit_expr_mut(&mut loop_.incre_expr);
+    v.visit_for_update_mut(&mut loop_.update);
+    v.visit_block_mut(&mut loop_.block);
+}
+
+pub fn walk_while_mut<V: VisitorMut + ?Sized>(v: &mut V, while_: &mut While) {
+    v.visit_expr_mut(&mut while_.expr);
+    v.visit_block_mut(&mut while_.block);
+}
+
+pub fn walk_return_mut<V: VisitorMut + ?Sized>(v: &mut V, return_: &mut Return) {
+    if let Some(e) = &mut return_.expr {
+        v.visit_expr_mut(e);
+    }
+}
+
+pub fn walk_for_update_mut<V: VisitorMut + ?Sized>(v: &mut V, update: &mut ForUpdate) {
+    v.visit_location_mut(&mut update.location);
+    if let ForUpdateExpr::AssignExpr(e) = &mut update.update_expr {
+        v.visit_expr_mut(&mut e.expr);
+    }
+}
+
+pub fn walk_method_call_mut<V: VisitorMut + ?Sized>(v: &mut V, call: &mut MethodCall) {
+    match call {
+        MethodCall::Method(m) => {
+            v.visit_id_mut(&mut m.name.id);
+            for arg in &mut m.args {
+                v.visit_expr_mut(arg);
+            }
+        }
+        MethodCall::Callout(c) => {
+            for arg in &mut c.args {
+                v.visit_import_arg_mut(arg);
+            }
+        }
+    }
+}
+
+pub fn walk_import_arg_mut<V: VisitorMut + ?Sized>(v: &mut V, arg: &mut ImportArg) {
+    if let ImportArg::Expr(e) = arg {
+        v.visit_expr_mut(e);
+    }
+}
+
+pub fn walk_location_mut<V: VisitorMut + ?Sized>(v: &mut V, location: &mut Location) {
+    match location {
+        Location::Id(id) => v.visit_id_mut(id),
+        Location::IdExpr(e) => {
+            v.visit_id_mut(&mut e.id);
+            v.visit_expr_mut(&mut e.array_len);
+        }
+    }
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(v: &mut V, expr: &mut Expr) {
+    match &mut expr.node {
+        Expr_::Location(l) => v.visit_location_mut(l),
+        Expr_::MethodCall(m) => v.visit_method_call_mut(m),
+        Expr_::Literal(l) => v.visit_literal_mut(l),
+        Expr_::LenId(id) => v.visit_id_mut(id),
+        Expr_::Unary(u) => v.visit_expr_mut(&mut u.expr),
+        Expr_::Binary(b) => {
+            v.visit_expr_mut(&mut b.lhs);
+            v.visit_expr_mut(&mut b.rhs);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::printer::{ print_program, PrintOptions };
+    use crate::parser::parse;
+
+    // collects every identifier in source order
+    struct Ids(Vec<String>);
+
+    impl Visitor for Ids {
+        fn visit_id(&mut self, id: &Id) {
+            self.0.push(id.node.clone());
+        }
+    }
+
+    #[test]
+    fn test_visitor_order() {
+        let program = parse("import f; int a[2];
+            int g(int x) { if (x > 0) { return f(x - 1, a[x]); } for (i = 0; i < len(a); i += x) { callout(\"p\", i); } }")
+            .unwrap();
+        let mut ids = Ids(Vec::new());
+        ids.visit_program(&program);
+        assert_eq!(ids.0, vec!["f", "a", "g", "x", "x", "f", "x", "a", "x", "i", "i", "a", "i", "x", "i"]);
+    }
+
+    // a visitor that stops descending into some nodes
+    struct CallCounter {
+        calls: usize,
+    }
+
+    impl Visitor for CallCounter {
+        fn visit_method_call(&mut self, call: &MethodCall) {
+            self.calls += 1;
+            walk_method_call(self, call);
+        }
+
+        fn visit_while(&mut self, _while: &While) {}
+    }
+
+    #[test]
+    fn test_visitor_override() {
+        let program = parse("void main() { f(g(1)); while (h()) { f(1); } }").unwrap();
+        let mut counter = CallCounter { calls: 0 };
+        counter.visit_program(&program);
+        assert_eq!(counter.calls, 2);
+    }
+
+    // folds `x + 0` into `x`
+    struct AddZero;
+
+    impl VisitorMut for AddZero {
+        fn visit_expr_mut(&mut self, expr: &mut Expr) {
+            walk_expr_mut(self, expr);
+            if let Expr_::Binary(Binary { lhs, rhs, op: BinaryOp::ArithOp(ArithOp::Add) }) = &expr.node {
+                if matches!(&rhs.node, Expr_::Literal(Literal::IntLiteral(i)) if i.value() == Some(0)) {
+                    *expr = lhs.clone();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_visitor_mut() {
+        let mut program = parse("void main() { x = (y + 0) * (z + 0 + 0); a[i + 0] = 1; }").unwrap();
+        AddZero.visit_program_mut(&mut program);
+        let printed = print_program(&program, &PrintOptions::default());
+        assert_eq!(printed, "void main() {\n    x = y * z;\n    a[i] = 1;\n}\n");
+    }
+}
@@ -0,0 +1,579 @@
+//! x86-64 code generation from three-address code, as GNU `as` (AT&T
+//! syntax) for the System V ABI.
+//!
+//! Code is unoptimized: every local and temporary has its own stack slot,
+//! and each instruction loads its operands into registers, computes and
+//! stores the result back. `int`s are 32 bits, so arithmetic is done on the
+//! 32-bit registers; array elements take 4 bytes.
+//!
+//! Symbols are chosen so that Decaf names can't clash with each other or
+//! with the C library: methods other than `main` are `__decaf_<name>`,
+//! globals are `.Lglob.<name>`, labels are `.L<method>.<n>`, run-time
+//! error handlers `.Ltrap.<method>.<kind><n>`, strings `.Lstr<n>` and
+//! run-time error messages `.Lmsg<n>`.
+
+use std::fmt::Write;
+
+use crate::tac::{ self, Arg, BinaryOp, Callee, Instr, Names, Operand, UnaryOp, Var };
+
+const ARG_REGS: [&str; 6] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+
+#[derive(Debug, Clone)]
+pub struct CodegenOptions {
+    /// Check that array subscripts are in bounds.
+    pub bounds_check: bool,
+}
+
+impl Default for CodegenOptions {
+    fn default() -> Self {
+        Self { bounds_check: true }
+    }
+}
+
+/// Generates the assembly for `program`, ready to be assembled and linked
+/// with `gcc`.
+///
+/// The run-time checks required by the spec print an error to `stderr` and
+/// exit: with `-1` when an array subscript is out of bounds, and with `-2`
+/// when a method that returns a value reaches its end. Division by zero
+/// exits with `1` in the same way, and `INT_MIN / -1` wraps around, like in
+/// the interpreter.
+pub fn emit_program(program: &tac::Program, options: &CodegenOptions) -> String {
+    // error messages are only known once the code is generated
+    let mut text = String::new();
+    let mut messages = Vec::new();
+    for method in &program.methods {
+        MethodCodegen::new(program, method, options, &mut text, &mut messages).emit();
+    }
+
+    let mut out = String::new();
+    if !program.globals.is_empty() {
+        writeln!(out, "    .bss").unwrap();
+        for global in &program.globals {
+            writeln!(out, "    .align 8").unwrap();
+            writeln!(out, ".Lglob.{}:", global.name).unwrap();
+            writeln!(out, "    .zero {}", size(global.len)).unwrap();
+        }
+    }
+
+    if !program.strings.is_empty() || !messages.is_empty() {
+        writeln!(out, "    .section .rodata").unwrap();
+        for (i, s) in program.strings.iter().enumerate() {
+            writeln!(out, ".Lstr{}:", i).unwrap();
+            writeln!(out, "    .string \"{}\"", escape_asm(s)).unwrap();
+        }
+        for (i, s) in messages.iter().enumerate() {
+            writeln!(out, ".Lmsg{}:", i).unwrap();
+            writeln!(out, "    .string \"{}\"", escape_asm(s)).unwrap();
+        }
+    }
+
+    writeln!(out, "    .text").unwrap();
+    out.push_str(&text);
+    writeln!(out, "    .section .note.GNU-stack,\"\",@progbits").unwrap();
+    out
+}
+
+// the bytes taken by a variable, kept a multiple of 8
+fn size(len: Option<u32>) -> u32 {
+    match len {
+        Some(len) => (len * 4).div_ceil(8) * 8,
+        None => 8,
+    }
+}
+
+fn escape_asm(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '"' => "\\\"".to_string(),
+            '\\' => "\\\\".to_string(),
+            '\n' => "\\n".to_string(),
+            '\t' => "\\t".to_string(),
+            c if c.is_ascii_graphic() || c == ' ' => c.to_string(),
+            c => format!("\\{:03o}", c as u32 & 0xff),
+        })
+        .collect()
+}
+
+struct MethodCodegen<'a> {
+    names: Names<'a>,
+    options: &'a CodegenOptions,
+    out: &'a mut String,
+    messages: &'a mut Vec<String>,
+    // the arrays accessed, with the index of their out-of-bounds message
+    bounds_traps: Vec<(Var, usize)>,
+    // the index of the division-by-zero message, once a division is made
+    div_trap: Option<usize>,
+    // the offset from `%rbp` of each local
+    offsets: Vec<i32>,
+    frame_size: u32,
+}
+
+impl<'a> MethodCodegen<'a> {
+    fn new(
+        program: &'a tac::Program,
+        method: &'a tac::Method,
+        options: &'a CodegenOptions,
+        out: &'a mut String,
+        messages: &'a mut Vec<String>,
+    ) -> Self {
+        let mut offsets = Vec::new();
+        let mut frame_size = 0;
+        for local in &method.locals {
+            frame_size += size(local.len);
+            offsets.push(-(frame_size as i32));
+        }
+        Self {
+            names: Names { program, method },
+            options,
+            out,
+            messages,
+            bounds_traps: Vec::new(),
+            div_trap: None,
+            offsets,
+            frame_size: frame_size.div_ceil(16) * 16,
+        }
+    }
+
+    fn line(&mut self, line: impl AsRef<str>) {
+        writeln!(self.out, "    {}", line.as_ref()).unwrap();
+    }
+
+    fn label(&self, label: tac::Label) -> String {
+        format!(".L{}.{}", self.names.method.name, label.0)
+    }
+
+    fn addr(&self, var: Var) -> String {
+        match var {
+            Var::Global(g) => format!(".Lglob.{}(%rip)", self.names.program.globals[g.index()].name),
+            Var::Local(l) => format!("{}(%rbp)", self.offsets[l.index()]),
+        }
+    }
+
+    fn load(&mut self, operand: Operand, reg: &str) {
+        match operand {
+            Operand::Const(c) => self.line(format!("movl ${}, {}", c, reg)),
+            Operand::Var(v) => self.line(format!("movl {}, {}", self.addr(v), reg)),
+        }
+    }
+
+    fn store(&mut self, reg: &str, var: Var) {
+        self.line(format!("movl {}, {}", reg, self.addr(var)));
+    }
+
+    fn message(&mut self, message: String) -> usize {
+        self.messages.push(message);
+        self.messages.len() - 1
+    }
+
+    // leaves the address of `array[index]` in `%rcx` and `%rax`
+    fn element(&mut self, array: Var, index: Operand) {
+        self.load(index, "%eax");
+        if self.options.bounds_check {
+            // negative indices are too large when compared unsigned
+            let len = self.len(array);
+            let trap = self.bounds_trap(array);
+            self.line(format!("cmpl ${}, %eax", len));
+            self.line(format!("jae .Ltrap.{}.oob{}", self.names.method.name, trap));
+        }
+        self.line("cltq");
+        self.line(format!("leaq {}, %rcx", self.addr(array)));
+    }
+
+    fn len(&self, array: Var) -> u32 {
+        let len = match array {
+            Var::Global(g) => self.names.program.globals[g.index()].len,
+            Var::Local(l) => self.names.method.locals[l.index()].len,
+        };
+        len.expect("indexing a scalar")
+    }
+
+    // the index of the out-of-bounds handler for `array`
+    fn bounds_trap(&mut self, array: Var) -> usize {
+        if let Some(i) = self.bounds_traps.iter().position(|(a, _)| *a == array) {
+            return i;
+        }
+        // locals are printed with the suffix that made them unique
+        let name = self.names.var(array);
+        let name = name.trim_start_matches('@').split('.').next().unwrap();
+        let message = format!(
+            "runtime error: index %d is out of bounds for `{}[{}]` in method `{}`\n",
+            name,
+            self.len(array),
+            self.names.method.name,
+        );
+        let message = self.message(message);
+        self.bounds_traps.push((array, message));
+        self.bounds_traps.len() - 1
+    }
+
+    // prints `message` to `stderr`, with `%edx` as its argument, and exits
+    // with `status`
+    fn trap(&mut self, message: usize, status: i32) {
+        self.line("movq stderr@GOTPCREL(%rip), %rdi");
+        self.line("movq (%rdi), %rdi");
+        self.line(format!("leaq .Lmsg{}(%rip), %rsi", message));
+        self.line("movl $0, %eax");
+        self.line("call fprintf");
+        self.line(format!("movl ${}, %edi", status));
+        self.line("call exit");
+    }
+
+    fn emit(mut self) {
+        let method = self.names.method;
+        if method.name == "main" {
+            writeln!(self.out, "    .globl main").unwrap();
+        }
+        writeln!(self.out, "    .type {}, @function", symbol(&method.name)).unwrap();
+        writeln!(self.out, "{}:", symbol(&method.name)).unwrap();
+        self.line("pushq %rbp");
+        self.line("movq %rsp, %rbp");
+        if self.frame_size > 0 {
+            self.line(format!("subq ${}, %rsp", self.frame_size));
+        }
+
+        // arguments past the sixth were pushed by the caller, above the
+        // return address
+        for i in 0..method.params {
+            let param = Var::Local(tac::LocalId(i as u32));
+            match ARG_REGS.get(i) {
+                Some(reg) => self.store(&dword(reg), param),
+                None => {
+                    self.line(format!("movl {}(%rbp), %eax", 16 + 8 * (i - ARG_REGS.len())));
+                    self.store("%eax", param);
+                }
+            }
+        }
+
+        for instr in &method.body {
+            self.emit_instr(instr);
+        }
+
+        // the index is still in `%eax`
+        for (i, (_, message)) in self.bounds_traps.clone().into_iter().enumerate() {
+            writeln!(self.out, ".Ltrap.{}.oob{}:", method.name, i).unwrap();
+            self.line("movl %eax, %edx");
+            self.trap(message, -1);
+        }
+        if let Some(message) = self.div_trap {
+            writeln!(self.out, ".Ltrap.{}.div0:", method.name).unwrap();
+            self.trap(message, 1);
+        }
+    }
+
+    fn emit_instr(&mut self, instr: &Instr) {
+        if let Instr::Label(l) = instr {
+            writeln!(self.out, "{}:", self.label(*l)).unwrap();
+            return;
+        }
+
+        let comment = self.names.instr(instr);
+        self.line(format!("# {}", comment));
+        match instr {
+            Instr::Label(_) => unreachable!(),
+            Instr::Copy { dst, src } => {
+                self.load(*src, "%eax");
+                self.store("%eax", *dst);
+            }
+            Instr::Unary { dst, op, src } => {
+                self.load(*src, "%eax");
+                match op {
+                    UnaryOp::Neg => self.line("negl %eax"),
+                    UnaryOp::Not => self.line("xorl $1, %eax"),
+                }
+                self.store("%eax", *dst);
+            }
+            Instr::Binary { dst, op, lhs, rhs } => {
+                self.load(*lhs, "%eax");
+                self.load(*rhs, "%ecx");
+                self.emit_binary(*op);
+                self.store("%eax", *dst);
+            }
+            Instr::Load { dst, array, index } => {
+                self.element(*array, *index);
+                self.line("movl (%rcx,%rax,4), %eax");
+                self.store("%eax", *dst);
+            }
+            Instr::Store { array, index, src } => {
+                self.element(*array, *index);
+                self.load(*src, "%edx");
+                self.line("movl %edx, (%rcx,%rax,4)");
+            }
+            Instr::Jump(l) => self.line(format!("jmp {}", self.label(*l))),
+            Instr::Branch { cond, if_true, if_false } => {
+                self.load(*cond, "%eax");
+                self.line("cmpl $0, %eax");
+                self.line(format!("jne {}", self.label(*if_true)));
+                self.line(format!("jmp {}", self.label(*if_false)));
+            }
+            Instr::Call { dst, callee, args } => {
+                self.emit_call(callee, args);
+                if let Some(dst) = dst {
+                    self.store("%eax", *dst);
+                }
+            }
+            Instr::Return(None) if self.names.method.returns_value => {
+                let message = format!(
+                    "runtime error: method `{}` reached its end without returning a value\n",
+                    self.names.method.name,
+                );
+                let message = self.message(message);
+                self.trap(message, -2);
+            }
+            Instr::Return(val) => {
+                match val {
+                    Some(val) => self.load(*val, "%eax"),
+                    // `main` is called from C, which expects an exit status
+                    None if self.names.method.name == "main" => self.line("movl $0, %eax"),
+                    None => {}
+                }
+                self.line("leave");
+                self.line("ret");
+            }
+        }
+    }
+
+    // `%eax = %eax op %ecx`
+    fn emit_binary(&mut self, op: BinaryOp) {
+        let set = match op {
+            BinaryOp::Add => return self.line("addl %ecx, %eax"),
+            BinaryOp::Sub => return self.line("subl %ecx, %eax"),
+            BinaryOp::Mul => return self.line("imull %ecx, %eax"),
+            BinaryOp::Div | BinaryOp::Mod => {
+                if self.div_trap.is_none() {
+                    let message = format!("runtime error: division by zero in method `{}`\n", self.names.method.name);
+                    self.div_trap = Some(self.message(message));
+                }
+                self.line("testl %ecx, %ecx");
+                self.line(format!("je .Ltrap.{}.div0", self.names.method.name));
+                // `idivl` faults on `INT_MIN / -1`, which wraps around instead
+                self.line("cmpl $-1, %ecx");
+                self.line("jne 1f");
+                self.line("negl %eax");
+                self.line("movl $0, %edx");
+                self.line("jmp 2f");
+                writeln!(self.out, "1:").unwrap();
+                self.line("cltd");
+                self.line("idivl %ecx");
+                writeln!(self.out, "2:").unwrap();
+                if op == BinaryOp::Mod {
+                    self.line("movl %edx, %eax");
+                }
+                return;
+            }
+            BinaryOp::Lt => "setl",
+            BinaryOp::Le => "setle",
+            BinaryOp::Gt => "setg",
+            BinaryOp::Ge => "setge",
+            BinaryOp::Eq => "sete",
+            BinaryOp::Ne => "setne",
+        };
+        self.line("cmpl %ecx, %eax");
+        self.line(format!("{} %al", set));
+        self.line("movzbl %al, %eax");
+    }
+
+    fn emit_call(&mut self, callee: &Callee, args: &[Arg]) {
+        // the stack must be 16-byte aligned at the call
+        let pushed = args.len().saturating_sub(ARG_REGS.len());
+        if pushed % 2 == 1 {
+            self.line("subq $8, %rsp");
+        }
+        for arg in args.iter().skip(ARG_REGS.len()).rev() {
+            self.load_arg(arg, "%rax");
+            self.line("pushq %rax");
+        }
+        for (arg, reg) in args.iter().zip(ARG_REGS) {
+            self.load_arg(arg, reg);
+        }
+
+        let name = match callee {
+            Callee::Method(name) => symbol(name),
+            Callee::Extern(name) => {
+                // no vector registers are used by variadic C functions
+                self.line("movl $0, %eax");
+                name.clone()
+            }
+        };
+        self.line(format!("call {}", name));
+
+        let popped = pushed.div_ceil(2) * 2;
+        if popped > 0 {
+            self.line(format!("addq ${}, %rsp", 8 * popped));
+        }
+    }
+
+    // loads a whole 64-bit register, as C expects of `long`s and pointers
+    fn load_arg(&mut self, arg: &Arg, reg: &str) {
+        match arg {
+            Arg::Operand(Operand::Const(c)) => self.line(format!("movq ${}, {}", c, reg)),
+            Arg::Operand(Operand::Var(v)) => self.line(format!("movslq {}, {}", self.addr(*v), reg)),
+            Arg::String(s) => self.line(format!("leaq .Lstr{}(%rip), {}", s.0, reg)),
+        }
+    }
+}
+
+// the symbol of a Decaf method; `main` is called by the C runtime
+fn symbol(method: &str) -> String {
+    match method {
+        "main" => "main".to_string(),
+        name => format!("__decaf_{}", name),
+    }
+}
+
+// the 32-bit half of a 64-bit register
+fn dword(reg: &str) -> String {
+    match reg {
+        "%rdi" => "%edi".to_string(),
+        "%rsi" => "%esi".to_string(),
+        "%rdx" => "%edx".to_string(),
+        "%rcx" => "%ecx".to_string(),
+        r => format!("{}d", r),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile;
+    use crate::interpreter::{ CalloutTable, Interpreter };
+    use crate::semantic_analyzer::lint::LintConfig;
+    use crate::tac::lower;
+    use crate::test_util::get_current_dir;
+    use std::env;
+    use std::fs::{ read_to_string, write };
+    use std::path::PathBuf;
+    use std::process::{ Command, Output };
+
+    fn testcase(filename: &str) -> PathBuf {
+        [&get_current_dir(), "src", "codegen", "testcases", filename].iter().collect()
+    }
+
+    // Builds the program in `path` with `gcc` and runs it, or returns `None`
+    // when there is no `gcc` to build with.
+    fn build_and_run(path: &PathBuf, options: &CodegenOptions) -> Option<Output> {
+        Command::new("gcc").arg("--version").output().ok()?;
+
+        let code = read_to_string(path).unwrap();
+        let ir = compile(&code, &LintConfig::default()).unwrap().ir;
+        let name = path.file_stem().unwrap().to_str().unwrap();
+        let dir = env::temp_dir();
+        let asm = dir.join(format!("decafc-{}-{}.s", name, std::process::id()));
+        let exe = dir.join(format!("decafc-{}-{}", name, std::process::id()));
+        write(&asm, emit_program(&lower(&ir), options)).unwrap();
+
+        let gcc = Command::new("gcc").arg(&asm).arg("-o").arg(&exe).output().unwrap();
+        assert!(gcc.status.success(), "{}", String::from_utf8_lossy(&gcc.stderr));
+        let output = Command::new(&exe).output().unwrap();
+        let _ = std::fs::remove_file(asm);
+        let _ = std::fs::remove_file(exe);
+        Some(output)
+    }
+
+    macro_rules! test_codegen {
+        ( $testname:ident, $filename:expr ) => {
+            #[test]
+            fn $testname() {
+                let Some(output) = build_and_run(&testcase($filename), &CodegenOptions::default()) else { return };
+                let expected = read_to_string(testcase(concat!($filename, ".out"))).unwrap();
+                assert!(output.status.success());
+                assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+            }
+        };
+    }
+
+    test_codegen!(test_codegen_calls, "calls.dcf");
+    test_codegen!(test_codegen_arith, "arith.dcf");
+    test_codegen!(test_codegen_libc_names, "libc-names.dcf");
+    test_codegen!(test_codegen_trap_labels, "trap-labels.dcf");
+
+    #[test]
+    fn test_codegen_quicksort() {
+        let path: PathBuf = [&get_current_dir(), "src", "semantic_analyzer", "testcases", "legal-01.dcf"].iter().collect();
+        let Some(output) = build_and_run(&path, &CodegenOptions::default()) else { return };
+        assert!(output.status.success());
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let (before, after) = stdout.split_once("after sort").unwrap();
+        let numbers = |s: &str| -> Vec<i64> { s.lines().filter_map(|l| l.trim().parse().ok()).collect() };
+        let mut sorted = numbers(before.split_once("before sort:").unwrap().1);
+        sorted.sort();
+        assert_eq!(sorted.len(), 10);
+        assert_eq!(numbers(after), sorted);
+    }
+
+    #[test]
+    fn test_codegen_out_of_bounds() {
+        let Some(output) = build_and_run(&testcase("out-of-bounds.dcf"), &CodegenOptions::default()) else { return };
+        assert_eq!(output.status.code(), Some(255));
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "0\n1\n2\n3\n");
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            "runtime error: index 4 is out of bounds for `a[4]` in method `main`\n"
+        );
+    }
+
+    #[test]
+    fn test_codegen_trap_libc_names() {
+        let Some(output) = build_and_run(&testcase("trap-libc-names.dcf"), &CodegenOptions::default()) else { return };
+        assert_eq!(output.status.code(), Some(255));
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            "runtime error: index 2 is out of bounds for `a[2]` in method `main`\n"
+        );
+    }
+
+    #[test]
+    fn test_codegen_missing_return() {
+        let Some(output) = build_and_run(&testcase("missing-return.dcf"), &CodegenOptions::default()) else { return };
+        assert_eq!(output.status.code(), Some(254));
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "1\n-1\n");
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            "runtime error: method `sign` reached its end without returning a value\n"
+        );
+    }
+
+    #[test]
+    fn test_codegen_no_bounds_check() {
+        let code = read_to_string(testcase("out-of-bounds.dcf")).unwrap();
+        let program = lower(&compile(&code, &LintConfig::default()).unwrap().ir);
+        let asm = emit_program(&program, &CodegenOptions { bounds_check: false });
+        assert!(!asm.contains("out of bounds"));
+        assert!(emit_program(&program, &CodegenOptions::default()).contains("out of bounds"));
+    }
+
+    // the interpreter is the reference for what programs do
+    #[test]
+    fn test_codegen_matches_interpreter() {
+        let legal_01: PathBuf = [&get_current_dir(), "src", "semantic_analyzer", "testcases", "legal-01.dcf"].iter().collect();
+        let paths = [
+            "calls.dcf",
+            "arith.dcf",
+            "libc-names.dcf",
+            "trap-libc-names.dcf",
+            "trap-labels.dcf",
+            "out-of-bounds.dcf",
+            "missing-return.dcf",
+            "div-zero.dcf",
+            "mod-zero.dcf",
+        ]
+        .map(testcase);
+        for path in paths.iter().chain([&legal_01]) {
+            let Some(output) = build_and_run(path, &CodegenOptions::default()) else { return };
+
+            let code = read_to_string(path).unwrap();
+            let ir = compile(&code, &LintConfig::default()).unwrap().ir;
+            let mut interpreter = Interpreter::new(&ir, CalloutTable::default(), Vec::new());
+            let status = interpreter.run().map_or_else(|e| e.exit_code() & 0xff, |()| 0);
+            assert_eq!(output.stdout, interpreter.into_output(), "{}", path.display());
+            assert_eq!(output.status.code(), Some(status), "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn test_escape_asm() {
+        assert_eq!(escape_asm("a \"b\"\\\n\t\x07"), "a \\\"b\\\"\\\\\\n\\t\\007");
+    }
+}
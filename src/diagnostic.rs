@@ -0,0 +1,167 @@
+use std::fmt::Write;
+
+use crate::span::{ LineIndex, Span };
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub primary: bool,
+}
+
+/// A message about the source program, rendered like rustc does:
+///
+/// ```text
+/// error: mismatched types
+///  --> foo.dcf:3:9
+///   |
+/// 3 |     a = true;
+///   |         ^^^^ expected `int`, found `bool`
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(message)
+        }
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label { span, message: message.into(), primary: true });
+        self
+    }
+
+    pub fn with_secondary_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label { span, message: message.into(), primary: false });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        self.labels.iter().find(|l| l.primary).or_else(|| self.labels.first()).map(|l| l.span)
+    }
+
+    pub fn render(&self, file_name: &str, src: &str) -> String {
+        let index = LineIndex::new(src);
+        let mut out = String::new();
+
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        writeln!(out, "{}: {}", severity, self.message).unwrap();
+
+        let mut labels: Vec<&Label> = self.labels.iter().collect();
+        labels.sort_by_key(|l| (l.span.start, !l.primary));
+
+        let gutter = labels
+            .iter()
+            .map(|l| index.line_col(l.span.start).line.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let pad = " ".repeat(gutter);
+
+        match self.span() {
+            Some(span) => {
+                let pos = index.line_col(span.start);
+                writeln!(out, "{}--> {}:{}:{}", pad, file_name, pos.line, pos.col).unwrap();
+            }
+            None => writeln!(out, "{}--> {}", pad, file_name).unwrap(),
+        }
+
+        if !labels.is_empty() {
+            writeln!(out, "{} |", pad).unwrap();
+        }
+        let mut last_line = None;
+        for label in labels {
+            let start = index.line_col(label.span.start);
+            let text = index.line(start.line);
+            if last_line != Some(start.line) {
+                writeln!(out, "{:>width$} | {}", start.line, text, width = gutter).unwrap();
+                last_line = Some(start.line);
+            }
+
+            // spans reaching past the end of the line are underlined up to it
+            let end = index.line_col(label.span.end);
+            let end_col = if end.line == start.line { end.col } else { text.chars().count() + 1 };
+            let width = end_col.saturating_sub(start.col).max(1);
+            let marker = if label.primary { "^" } else { "-" };
+            let underline = format!("{}{}", " ".repeat(start.col - 1), marker.repeat(width));
+            if label.message.is_empty() {
+                writeln!(out, "{} | {}", pad, underline).unwrap();
+            } else {
+                writeln!(out, "{} | {} {}", pad, underline, label.message).unwrap();
+            }
+        }
+
+        if !self.notes.is_empty() {
+            writeln!(out, "{} |", pad).unwrap();
+        }
+        for note in &self.notes {
+            writeln!(out, "{} = note: {}", pad, note).unwrap();
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let src = "void main() {\n    int a;\n    a = true;\n}\n";
+        let d = Diagnostic::error("mismatched types")
+            .with_label(Span::new(33, 37), "expected `int`, found `bool`")
+            .with_secondary_label(Span::new(22, 23), "`a` declared here");
+        let expected = "\
+error: mismatched types
+ --> main.dcf:3:9
+  |
+2 |     int a;
+  |         - `a` declared here
+3 |     a = true;
+  |         ^^^^ expected `int`, found `bool`
+";
+        assert_eq!(d.render("main.dcf", src), expected);
+    }
+
+    #[test]
+    fn test_render_without_span() {
+        let d = Diagnostic::error("no `main` method").with_note("every program must declare `void main()`");
+        let expected = "\
+error: no `main` method
+--> main.dcf
+ |
+ = note: every program must declare `void main()`
+";
+        assert_eq!(d.render("main.dcf", ""), expected);
+    }
+}
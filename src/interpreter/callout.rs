@@ -0,0 +1,257 @@
+//! The functions that `callout`s and imports reach when a program is
+//! interpreted, standing in for the C library.
+
+use std::cell::RefCell;
+use std::collections::{ HashMap, VecDeque };
+use std::io::Write;
+use std::iter::Peekable;
+use std::rc::Rc;
+use std::str::Chars;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CalloutArg<'a> {
+    Int(i32),
+    Str(&'a str),
+}
+
+/// Gets the program output and the arguments, and returns the value of the
+/// call or an error message.
+pub type Callout = Box<dyn FnMut(&mut dyn Write, &[CalloutArg]) -> Result<i32, String>>;
+
+/// The callouts a program can make, by name.
+///
+/// The default table has `printf`, `putchar`, `puts`, and `random`/`rand`
+/// with `srandom`/`srand`, which give the same numbers as glibc's.
+pub struct CalloutTable {
+    callouts: HashMap<String, Callout>,
+}
+
+impl Default for CalloutTable {
+    fn default() -> Self {
+        let mut table = Self::empty();
+        table.insert("printf", |out, args| {
+            let text = match args {
+                [CalloutArg::Str(format), args @ ..] => printf(format, args)?,
+                _ => return Err("expected a format string".to_string()),
+            };
+            out.write_all(text.as_bytes()).map_err(|e| e.to_string())?;
+            Ok(text.len() as i32)
+        });
+        table.insert("putchar", |out, args| match args {
+            [CalloutArg::Int(c)] => {
+                out.write_all(&[*c as u8]).map_err(|e| e.to_string())?;
+                Ok(*c as u8 as i32)
+            }
+            _ => Err("expected a character".to_string()),
+        });
+        table.insert("puts", |out, args| match args {
+            [CalloutArg::Str(s)] => {
+                writeln!(out, "{}", s).map_err(|e| e.to_string())?;
+                Ok(0)
+            }
+            _ => Err("expected a string".to_string()),
+        });
+
+        let random = Rc::new(RefCell::new(Random::new(1)));
+        for name in ["random", "rand"] {
+            let random = Rc::clone(&random);
+            table.insert(name, move |_, _| Ok(random.borrow_mut().next()));
+        }
+        for name in ["srandom", "srand"] {
+            let random = Rc::clone(&random);
+            table.insert(name, move |_, args| match args {
+                [CalloutArg::Int(seed)] => {
+                    *random.borrow_mut() = Random::new(*seed as u32);
+                    Ok(0)
+                }
+                _ => Err("expected a seed".to_string()),
+            });
+        }
+        table
+    }
+}
+
+impl CalloutTable {
+    pub fn empty() -> Self {
+        Self { callouts: HashMap::new() }
+    }
+
+    /// Adds a callout, replacing any with the same name.
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        callout: impl FnMut(&mut dyn Write, &[CalloutArg]) -> Result<i32, String> + 'static,
+    ) {
+        self.callouts.insert(name.into(), Box::new(callout));
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Callout> {
+        self.callouts.get_mut(name)
+    }
+}
+
+/// glibc's `random`, an additive feedback generator over the last 34
+/// numbers.
+struct Random {
+    state: VecDeque<u32>,
+}
+
+impl Random {
+    fn new(seed: u32) -> Self {
+        let seed = if seed == 0 { 1 } else { seed };
+        let mut r = vec![seed as i32];
+        for i in 1..31 {
+            // 16807 * r[i - 1] % (2^31 - 1), without overflowing
+            let word = r[i - 1] as i64;
+            let mut word = 16807 * (word % 127773) - 2836 * (word / 127773);
+            if word < 0 {
+                word += 2147483647;
+            }
+            r.push(word as i32);
+        }
+        let mut state: VecDeque<u32> = r.iter().map(|&w| w as u32).collect();
+        for i in 0..3 {
+            state.push_back(state[i]);
+        }
+
+        let mut random = Self { state };
+        for _ in 0..310 {
+            random.next();
+        }
+        random
+    }
+
+    fn next(&mut self) -> i32 {
+        let n = self.state.len();
+        let word = self.state[n - 31].wrapping_add(self.state[n - 3]);
+        self.state.pop_front();
+        self.state.push_back(word);
+        (word >> 1) as i32
+    }
+}
+
+/// Formats like C's `printf`, for the conversions `d`, `i`, `u`, `x`, `X`,
+/// `o`, `c`, `s` and `%`, with flags, width and precision.
+fn printf(format: &str, args: &[CalloutArg]) -> Result<String, String> {
+    let mut out = String::new();
+    let mut args = args.iter();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+
+        let mut flags = String::new();
+        while let Some(&f) = chars.peek().filter(|f| "-+ #0".contains(**f)) {
+            flags.push(f);
+            chars.next();
+        }
+        let width = number(&mut chars).unwrap_or(0);
+        let precision = if chars.peek() == Some(&'.') {
+            chars.next();
+            Some(number(&mut chars).unwrap_or(0))
+        } else {
+            None
+        };
+        while chars.peek().is_some_and(|c| "hlqjzt".contains(*c)) {
+            chars.next();
+        }
+
+        let conversion = chars.next().ok_or("incomplete conversion at the end of the format")?;
+        if conversion == '%' {
+            out.push('%');
+            continue;
+        }
+        let arg = args.next().ok_or_else(|| format!("no argument for `%{}`", conversion))?;
+        let (sign, body) = match (conversion, arg) {
+            ('d' | 'i', CalloutArg::Int(n)) => {
+                let sign = if *n < 0 {
+                    "-"
+                } else if flags.contains('+') {
+                    "+"
+                } else if flags.contains(' ') {
+                    " "
+                } else {
+                    ""
+                };
+                (sign, digits(n.unsigned_abs().to_string(), precision))
+            }
+            ('u', CalloutArg::Int(n)) => ("", digits((*n as u32).to_string(), precision)),
+            ('x', CalloutArg::Int(n)) => (if flags.contains('#') && *n != 0 { "0x" } else { "" }, digits(format!("{:x}", n), precision)),
+            ('X', CalloutArg::Int(n)) => (if flags.contains('#') && *n != 0 { "0X" } else { "" }, digits(format!("{:X}", n), precision)),
+            ('o', CalloutArg::Int(n)) => (if flags.contains('#') { "0" } else { "" }, digits(format!("{:o}", n), precision)),
+            ('c', CalloutArg::Int(n)) => ("", (*n as u8 as char).to_string()),
+            ('s', CalloutArg::Str(s)) => ("", s.chars().take(precision.unwrap_or(usize::MAX)).collect()),
+            ('d' | 'i' | 'u' | 'x' | 'X' | 'o' | 'c', CalloutArg::Str(_)) => {
+                return Err(format!("`%{}` expects an integer, found a string", conversion));
+            }
+            ('s', CalloutArg::Int(_)) => return Err("`%s` expects a string, found an integer".to_string()),
+            _ => return Err(format!("unsupported conversion `%{}`", conversion)),
+        };
+
+        let len = sign.chars().count() + body.chars().count();
+        let pad = width.saturating_sub(len);
+        if flags.contains('-') {
+            out.push_str(sign);
+            out.push_str(&body);
+            out.push_str(&" ".repeat(pad));
+        } else if flags.contains('0') && precision.is_none() && !matches!(conversion, 'c' | 's') {
+            out.push_str(sign);
+            out.push_str(&"0".repeat(pad));
+            out.push_str(&body);
+        } else {
+            out.push_str(&" ".repeat(pad));
+            out.push_str(sign);
+            out.push_str(&body);
+        }
+    }
+    Ok(out)
+}
+
+fn number(chars: &mut Peekable<Chars>) -> Option<usize> {
+    let mut n = None;
+    while let Some(d) = chars.peek().and_then(|d| d.to_digit(10)) {
+        n = Some(n.unwrap_or(0) * 10 + d as usize);
+        chars.next();
+    }
+    n
+}
+
+// pads `digits` with zeros to `precision` digits
+fn digits(digits: String, precision: Option<usize>) -> String {
+    match precision {
+        Some(0) if digits == "0" => String::new(),
+        Some(p) if p > digits.len() => "0".repeat(p - digits.len()) + &digits,
+        _ => digits,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_like_glibc() {
+        let mut random = Random::new(1);
+        let numbers: Vec<i32> = (0..3).map(|_| random.next()).collect();
+        assert_eq!(numbers, vec![1804289383, 846930886, 1681692777]);
+        assert_eq!(Random::new(0).next(), 1804289383);
+    }
+
+    #[test]
+    fn test_printf() {
+        use CalloutArg::*;
+        let cases: &[(&str, &[CalloutArg], &str)] = &[
+            ("%d|%5d|%-5d|%05d|%+d", &[Int(-3), Int(42), Int(42), Int(-42), Int(7)], "-3|   42|42   |-0042|+7"),
+            ("%x %X %#x %o %u", &[Int(255), Int(255), Int(255), Int(8), Int(-1)], "ff FF 0xff 10 4294967295"),
+            ("%c%s%.2s|%3s%%", &[Int(65), Str("bc"), Str("def"), Str("g")], "Abcde|  g%"),
+            ("%.3d %ld", &[Int(5), Int(6)], "005 6"),
+        ];
+        for (format, args, expected) in cases {
+            assert_eq!(printf(format, args).as_deref(), Ok(*expected), "{}", format);
+        }
+        assert!(printf("%d", &[]).is_err());
+        assert!(printf("%s", &[Int(1)]).is_err());
+    }
+}
@@ -0,0 +1,429 @@
+//! A tree-walking interpreter over the checked IR, to run programs without
+//! an assembler and to check the back-end against.
+//!
+//! `int`s are 32 bits and wrap around, `bool`s are `0` and `1`, and the
+//! run-time checks of the spec are made like in the generated code.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::ast::Span;
+use crate::diagnostic::Diagnostic;
+use crate::semantic_analyzer::ir::*;
+
+mod callout;
+pub use callout::{ Callout, CalloutArg, CalloutTable };
+
+/// The depth of method calls at which a program is stopped, well before the
+/// interpreter itself runs out of stack.
+pub const MAX_DEPTH: usize = 10000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuntimeError {
+    OutOfBounds { name: String, index: i32, len: i32, span: Span },
+    MissingReturn { name: String, span: Span },
+    DivisionByZero { span: Span },
+    UnknownCallout { name: String, span: Span },
+    CalloutFailed { name: String, message: String, span: Span },
+    StackOverflow { span: Span },
+}
+
+impl RuntimeError {
+    /// The exit status of the program, which the spec fixes for the
+    /// out-of-bounds and missing-return checks.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::OutOfBounds { .. } => -1,
+            Self::MissingReturn { .. } => -2,
+            _ => 1,
+        }
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            Self::OutOfBounds { name, index, len, span } => Diagnostic::error(format!("index {} is out of bounds for `{}[{}]`", index, name, len))
+                .with_label(*span, ""),
+            Self::MissingReturn { name, span } => Diagnostic::error(format!("method `{}` reached its end without returning a value", name))
+                .with_label(*span, ""),
+            Self::DivisionByZero { span } => Diagnostic::error("division by zero")
+                .with_label(*span, ""),
+            Self::UnknownCallout { name, span } => Diagnostic::error(format!("no callout named `{}`", name))
+                .with_label(*span, ""),
+            Self::CalloutFailed { name, message, span } => Diagnostic::error(format!("callout `{}` failed: {}", name, message))
+                .with_label(*span, ""),
+            Self::StackOverflow { span } => Diagnostic::error("too many nested method calls")
+                .with_label(*span, "")
+                .with_note(format!("calls can only be nested {} deep", MAX_DEPTH)),
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, RuntimeError>;
+
+// how a statement ends
+enum Control {
+    Next,
+    Break(LoopId),
+    Continue(LoopId),
+    Return(Option<i32>),
+}
+
+pub struct Interpreter<'a, W> {
+    ir: &'a IRRoot,
+    callouts: CalloutTable,
+    out: W,
+    globals: HashMap<VarId, Vec<i32>>,
+    // the variables of each method being run, scalars as one element
+    frames: Vec<HashMap<VarId, Vec<i32>>>,
+}
+
+impl<'a, W: Write> Interpreter<'a, W> {
+    /// Makes an interpreter for `ir`, whose callouts write to `out`.
+    pub fn new(ir: &'a IRRoot, callouts: CalloutTable, out: W) -> Self {
+        let globals = ir.root.field_decls.iter().map(|&var| (var, zeros(&ir[var]))).collect();
+        Self { ir, callouts, out, globals, frames: Vec::new() }
+    }
+
+    /// Runs `main`.
+    ///
+    /// Method calls of the program are nested calls in the interpreter, so
+    /// deep recursion needs a thread with a large stack.
+    pub fn run(&mut self) -> Result<()> {
+        let ir = self.ir;
+        let main = ir.root.method_decls.iter().find(|&&m| ir[m].name == "main").expect("no `main` in a checked program");
+        self.call_method(*main, Vec::new(), ir[*main].span)?;
+        Ok(())
+    }
+
+    pub fn into_output(self) -> W {
+        self.out
+    }
+
+    fn storage(&mut self, var: VarId) -> &mut Vec<i32> {
+        let storage = match self.ir[var].scope {
+            VarScope::Method(_) => self.frames.last_mut().unwrap().get_mut(&var),
+            _ => self.globals.get_mut(&var),
+        };
+        storage.unwrap()
+    }
+
+    // the position of `var[index]`, or of a scalar `var`
+    fn element(&self, var: VarId, index: Option<i32>, span: Span) -> Result<usize> {
+        let decl = &self.ir[var];
+        match (index, decl.arr_len) {
+            (Some(index), Some(len)) if index < 0 || index >= len => {
+                Err(RuntimeError::OutOfBounds { name: decl.name.clone(), index, len, span })
+            }
+            (Some(index), _) => Ok(index as usize),
+            (None, _) => Ok(0),
+        }
+    }
+
+    fn read(&mut self, var: VarId, index: Option<i32>, span: Span) -> Result<i32> {
+        let i = self.element(var, index, span)?;
+        Ok(self.storage(var)[i])
+    }
+
+    fn write(&mut self, var: VarId, index: Option<i32>, val: i32, span: Span) -> Result<()> {
+        let i = self.element(var, index, span)?;
+        self.storage(var)[i] = val;
+        Ok(())
+    }
+
+    fn call_method(&mut self, method: MethodId, args: Vec<i32>, span: Span) -> Result<i32> {
+        if self.frames.len() >= MAX_DEPTH {
+            return Err(RuntimeError::StackOverflow { span });
+        }
+        let decl = &self.ir[method];
+        self.frames.push(decl.args.iter().zip(args).map(|(&var, val)| (var, vec![val])).collect());
+        let control = self.exec_block(&decl.block);
+        self.frames.pop();
+
+        match control? {
+            Control::Return(val) => Ok(val.unwrap_or(0)),
+            _ if decl.return_type == ReturnType::Void => Ok(0),
+            _ => Err(RuntimeError::MissingReturn { name: decl.name.clone(), span: decl.span }),
+        }
+    }
+
+    fn exec_block(&mut self, block: &Block) -> Result<Control> {
+        // locals start out as zero every time their scope is entered
+        for &var in &block.field_decls {
+            let zeros = zeros(&self.ir[var]);
+            self.frames.last_mut().unwrap().insert(var, zeros);
+        }
+        for &stmt in &block.statements {
+            match self.exec_stmt(stmt)? {
+                Control::Next => {}
+                control => return Ok(control),
+            }
+        }
+        Ok(Control::Next)
+    }
+
+    // runs a loop body, and tells whether the loop goes on
+    fn exec_body(&mut self, loop_: LoopId, block: &Block) -> Result<Option<Control>> {
+        match self.exec_block(block)? {
+            Control::Break(l) if l == loop_ => Ok(Some(Control::Next)),
+            Control::Next => Ok(None),
+            Control::Continue(l) if l == loop_ => Ok(None),
+            control => Ok(Some(control)),
+        }
+    }
+
+    fn exec_stmt(&mut self, stmt: StmtId) -> Result<Control> {
+        let ir = self.ir;
+        let span = ir[stmt].span;
+        match &ir[stmt].stmt {
+            StmtType::Assign(assign) => {
+                let (op, val) = match &assign.assign_expr {
+                    AssignExpr::AssignOpExpr(a) => {
+                        let op = match &a.assign_op {
+                            AssignOp::Assign => None,
+                            AssignOp::CompoundAssignOp(op) => Some(op.clone()),
+                        };
+                        (op, Some(a.expr))
+                    }
+                    AssignExpr::Increment(inc) => (Some(increment_op(inc)), None),
+                };
+                self.update(&assign.dst, op, val, span)?;
+            }
+            StmtType::MethodCall(call) => {
+                self.call(call, span)?;
+            }
+            StmtType::IfElse(if_else) => {
+                if self.eval(if_else.cond)? != 0 {
+                    return self.exec_block(&if_else.if_block);
+                } else if let Some(block) = &if_else.else_block {
+                    return self.exec_block(block);
+                }
+            }
+            StmtType::For(for_) => {
+                let init = self.eval(for_.init_expr)?;
+                self.write(for_.var, None, init, span)?;
+                while self.eval(for_.cond)? != 0 {
+                    if let Some(control) = self.exec_body(for_.loop_, &for_.block)? {
+                        return Ok(control);
+                    }
+                    match &for_.update.update_expr {
+                        ForUpdateExpr::AssignExpr(a) => {
+                            self.update(&for_.update.dst, Some(a.compound_assign_op.clone()), Some(a.expr), span)?
+                        }
+                        ForUpdateExpr::Increment(inc) => self.update(&for_.update.dst, Some(increment_op(inc)), None, span)?,
+                    }
+                }
+            }
+            StmtType::While(while_) => {
+                while self.eval(while_.cond)? != 0 {
+                    if let Some(control) = self.exec_body(while_.loop_, &while_.block)? {
+                        return Ok(control);
+                    }
+                }
+            }
+            StmtType::Return(ret) => {
+                let val = ret.val.map(|v| self.eval(v)).transpose()?;
+                return Ok(Control::Return(val));
+            }
+            StmtType::Break(loop_) => return Ok(Control::Break(*loop_)),
+            StmtType::Continue(loop_) => return Ok(Control::Continue(*loop_)),
+        }
+        Ok(Control::Next)
+    }
+
+    // `dst = val`, `dst op= val` or, without a value, `dst op= 1`; the index
+    // of `dst` is evaluated once, before the value
+    fn update(&mut self, dst: &Location, op: Option<CompoundAssignOp>, val: Option<ExprId>, span: Span) -> Result<()> {
+        let index = dst.index.map(|i| self.eval(i)).transpose()?;
+        let val = match val {
+            Some(v) => self.eval(v)?,
+            None => 1,
+        };
+        let val = match op {
+            None => val,
+            Some(CompoundAssignOp::AddAssign) => self.read(dst.var, index, span)?.wrapping_add(val),
+            Some(CompoundAssignOp::SubAssign) => self.read(dst.var, index, span)?.wrapping_sub(val),
+        };
+        self.write(dst.var, index, val, span)
+    }
+
+    fn call(&mut self, call: &MethodCall, span: Span) -> Result<i32> {
+        match call {
+            MethodCall::Method(m) => {
+                let args = m.args.iter().map(|&a| self.eval(a)).collect::<Result<Vec<i32>>>()?;
+                self.call_method(m.method, args, span)
+            }
+            MethodCall::Callout(c) => {
+                let mut args = Vec::new();
+                for arg in &c.args {
+                    args.push(match arg {
+                        ImportArg::Expr(e) => CalloutArg::Int(self.eval(*e)?),
+                        ImportArg::StringLiteral(s) => CalloutArg::Str(s),
+                    });
+                }
+                let callout = self.callouts.get_mut(&c.name).ok_or_else(|| RuntimeError::UnknownCallout { name: c.name.clone(), span })?;
+                callout(&mut self.out, &args).map_err(|message| RuntimeError::CalloutFailed { name: c.name.clone(), message, span })
+            }
+        }
+    }
+
+    fn eval(&mut self, expr: ExprId) -> Result<i32> {
+        let ir = self.ir;
+        let span = ir[expr].span;
+        Ok(match &ir[expr].expr {
+            ExprType::Literal(Literal::IntLiteral(i)) => *i,
+            ExprType::Literal(Literal::CharLiteral(c)) => *c as i32,
+            ExprType::Literal(Literal::BoolLiteral(b)) => *b as i32,
+            ExprType::LenId(var) => ir[*var].arr_len.unwrap(),
+            ExprType::Location(l) => {
+                let index = l.index.map(|i| self.eval(i)).transpose()?;
+                self.read(l.var, index, span)?
+            }
+            ExprType::MethodCall(call) => self.call(call, span)?,
+            ExprType::Unary(u) => {
+                let val = self.eval(u.expr)?;
+                match u.op {
+                    UnaryOp::NegInt => val.wrapping_neg(),
+                    UnaryOp::NegBool => (val == 0) as i32,
+                }
+            }
+            ExprType::Binary(b) => {
+                let lhs = self.eval(b.lhs)?;
+                // `&&` and `||` only evaluate their right-hand side if needed
+                match b.op {
+                    BinaryOp::And if lhs == 0 => return Ok(0),
+                    BinaryOp::Or if lhs != 0 => return Ok(1),
+                    _ => {}
+                }
+                let rhs = self.eval(b.rhs)?;
+                match b.op {
+                    BinaryOp::Add => lhs.wrapping_add(rhs),
+                    BinaryOp::Sub => lhs.wrapping_sub(rhs),
+                    BinaryOp::Mul => lhs.wrapping_mul(rhs),
+                    BinaryOp::Div | BinaryOp::Mod if rhs == 0 => return Err(RuntimeError::DivisionByZero { span }),
+                    BinaryOp::Div => lhs.wrapping_div(rhs),
+                    BinaryOp::Mod => lhs.wrapping_rem(rhs),
+                    BinaryOp::GT => (lhs > rhs) as i32,
+                    BinaryOp::LT => (lhs < rhs) as i32,
+                    BinaryOp::LE => (lhs <= rhs) as i32,
+                    BinaryOp::GE => (lhs >= rhs) as i32,
+                    BinaryOp::EQ => (lhs == rhs) as i32,
+                    BinaryOp::NE => (lhs != rhs) as i32,
+                    BinaryOp::And | BinaryOp::Or => rhs,
+                }
+            }
+        })
+    }
+}
+
+fn zeros(decl: &VarDecl) -> Vec<i32> {
+    vec![0; decl.arr_len.unwrap_or(1) as usize]
+}
+
+fn increment_op(inc: &Increment) -> CompoundAssignOp {
+    match inc {
+        Increment::SelfAdd => CompoundAssignOp::AddAssign,
+        Increment::SelfSub => CompoundAssignOp::SubAssign,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile;
+    use crate::semantic_analyzer::lint::LintConfig;
+    use crate::test_util::get_current_dir;
+    use std::fs::read_to_string;
+    use std::path::PathBuf;
+
+    fn interpret(code: &str, callouts: CalloutTable) -> (String, Result<()>) {
+        let ir = compile(code, &LintConfig::default()).unwrap().ir;
+        let mut interpreter = Interpreter::new(&ir, callouts, Vec::new());
+        let result = interpreter.run();
+        (String::from_utf8(interpreter.into_output()).unwrap(), result)
+    }
+
+    // the outputs of the compiled testcases are the expected ones
+    macro_rules! test_interp {
+        ( $testname:ident, $filename:expr ) => {
+            #[test]
+            fn $testname() {
+                let dir: PathBuf = [&get_current_dir(), "src", "codegen", "testcases"].iter().collect();
+                let code = read_to_string(dir.join($filename)).unwrap();
+                let expected = read_to_string(dir.join(concat!($filename, ".out"))).unwrap();
+                assert_eq!(interpret(&code, CalloutTable::default()), (expected, Ok(())));
+            }
+        };
+    }
+
+    test_interp!(test_interp_calls, "calls.dcf");
+    test_interp!(test_interp_arith, "arith.dcf");
+
+    #[test]
+    fn test_interp_runtime_errors() {
+        let (output, result) = interpret("int a[3];\nvoid main() { int i; for (i = 0; i < 5; i++) { a[i] = 1; callout(\"printf\", \"%d\", i); } }", CalloutTable::default());
+        assert_eq!(output, "012");
+        let e = result.unwrap_err();
+        assert!(matches!(&e, RuntimeError::OutOfBounds { name, index: 3, len: 3, .. } if name == "a"));
+        assert_eq!(e.exit_code(), -1);
+
+        let (_, result) = interpret("int f(bool b) { if (b) { return 1; } }\nvoid main() { f(true); f(false); }", CalloutTable::default());
+        let e = result.unwrap_err();
+        assert!(matches!(&e, RuntimeError::MissingReturn { name, .. } if name == "f"));
+        assert_eq!(e.exit_code(), -2);
+
+        let (_, result) = interpret("void main() { int x; x = 7 % x; }", CalloutTable::default());
+        assert!(matches!(result, Err(RuntimeError::DivisionByZero { .. })));
+
+        let overflow = std::thread::Builder::new()
+            .stack_size(1 << 30)
+            .spawn(|| interpret("int f() { return f(); }\nvoid main() { f(); }", CalloutTable::default()).1)
+            .unwrap();
+        assert!(matches!(overflow.join().unwrap(), Err(RuntimeError::StackOverflow { .. })));
+
+        let (_, result) = interpret("void main() { callout(\"abort\"); }", CalloutTable::default());
+        assert!(matches!(result, Err(RuntimeError::UnknownCallout { name, .. }) if name == "abort"));
+    }
+
+    #[test]
+    fn test_interp_semantics() {
+        let code = "int g;\n\
+            int bump() { g += 1; return g; }\n\
+            int depth(int n) { int a[2]; a[1] += n; if (n > 0) { return depth(n - 1) + a[1]; } return a[1]; }\n\
+            void main() {\n\
+                int i, n;\n\
+                bool b;\n\
+                n = 2147483647;\n\
+                n += 1;\n\
+                callout(\"printf\", \"%d %d %d\\n\", n, -n, n / -1);\n\
+                b = false && bump() > 0;\n\
+                b = true || bump() > 0;\n\
+                callout(\"printf\", \"%d %d\\n\", g, g + bump() * 10);\n\
+                for (i = 0; i < 3; i++) { int x; x += i; callout(\"printf\", \"%d\", x); }\n\
+                while (true) { i -= 1; if (i == 1) { break; } continue; }\n\
+                callout(\"printf\", \" %d %d\\n\", i, depth(3));\n\
+            }";
+        let (output, result) = interpret(code, CalloutTable::default());
+        result.unwrap();
+        assert_eq!(output, "-2147483648 -2147483648 -2147483648\n0 10\n012 1 6\n");
+    }
+
+    #[test]
+    fn test_interp_callout_table() {
+        let mut callouts = CalloutTable::empty();
+        callouts.insert("twice", |out, args| match args {
+            [CalloutArg::Int(n)] => {
+                write!(out, "twice({})", n).unwrap();
+                Ok(2 * n)
+            }
+            _ => Err("expected one int".to_string()),
+        });
+        let (output, result) = interpret("import twice;\nvoid main() { twice(twice(3)); }", callouts);
+        result.unwrap();
+        assert_eq!(output, "twice(3)twice(6)");
+
+        let mut callouts = CalloutTable::empty();
+        callouts.insert("twice", |_, _| Err("expected one int".to_string()));
+        let (_, result) = interpret("void main() { callout(\"twice\", \"x\"); }", callouts);
+        assert!(matches!(result, Err(RuntimeError::CalloutFailed { message, .. }) if message == "expected one int"));
+    }
+}
@@ -1,23 +1,214 @@
 #[macro_use] extern crate lalrpop_util;
 
-mod ast;
-mod parser;
-mod semantic_analyzer;
+pub mod ast;
+pub mod codegen;
+pub mod diagnostic;
+pub mod interpreter;
+pub mod span;
+pub mod parser;
+pub mod scanner;
+pub mod semantic_analyzer;
+pub mod tac;
 lalrpop_mod!(#[allow(clippy::all)] decaf);
 
 #[cfg(test)]
 mod test_util;
 
-use parser::DecafParser;
-use semantic_analyzer::SemanticAnalyzer;
-
-pub fn compile(code: &str) {
-    let parsed = DecafParser::new().parse(code).unwrap();
-    println!("-----------------parse start--------------------");
-    println!("{:?}", parsed);
-    println!("-----------------parse end----------------------");
-    println!("------------semantic analyze start--------------");
-    let ir = SemanticAnalyzer::new().create_ir(parsed);
-    println!("{:?}", ir);
-    println!("------------semantic analyze end----------------");
+use std::fmt;
+use std::str::FromStr;
+
+use ast::printer::{ format_program, PrintOptions };
+use diagnostic::{ Diagnostic, Severity };
+use scanner::scan_comments;
+use semantic_analyzer::ir::IRRoot;
+use semantic_analyzer::lint::LintConfig;
+use semantic_analyzer::{ check_flow, check_lints, SemanticAnalyzer };
+
+pub use parser::parse;
+
+/// The stage at which compilation stops, as selected by `--target`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Scan,
+    Parse,
+    Inter,
+    Assembly,
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scan" => Ok(Target::Scan),
+            "parse" => Ok(Target::Parse),
+            "inter" => Ok(Target::Inter),
+            "assembly" => Ok(Target::Assembly),
+            _ => Err(format!("unknown target `{}`, expected one of: scan, parse, inter, assembly", s)),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Target::Scan => "scan",
+            Target::Parse => "parse",
+            Target::Inter => "inter",
+            Target::Assembly => "assembly",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// An alternative output of the `inter` stage, as selected by `--emit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    /// the control-flow graph of every method, in Graphviz format
+    CfgDot,
+}
+
+impl FromStr for Emit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cfg-dot" => Ok(Emit::CfgDot),
+            _ => Err(format!("unknown output `{}`, expected: cfg-dot", s)),
+        }
+    }
+}
+
+/// Everything the compiler produced for a program that passed all checks.
+#[derive(Debug)]
+pub struct CompiledArtifact {
+    pub ir: IRRoot,
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Parses and checks `code`, returning the checked IR along with the
+/// warnings enabled in `lints`, or every diagnostic that stopped compilation.
+pub fn compile(code: &str, lints: &LintConfig) -> Result<CompiledArtifact, Vec<Diagnostic>> {
+    let program = parse(code)?;
+    let mut ir = SemanticAnalyzer::new()
+        .create_ir(program)
+        .map_err(|errors| errors.iter().map(|e| e.to_diagnostic()).collect::<Vec<Diagnostic>>())?;
+
+    let mut warnings = check_flow(&mut ir);
+    warnings.extend(check_lints(&ir));
+    let mut warnings: Vec<Diagnostic> = warnings
+        .iter()
+        .filter(|w| lints.is_enabled(w.lint()))
+        .map(|w| w.to_diagnostic())
+        .collect();
+    warnings.sort_by_key(|d| d.span().map(|s| s.start));
+
+    if lints.deny_warnings && !warnings.is_empty() {
+        return Err(warnings
+            .into_iter()
+            .map(|d| Diagnostic { severity: Severity::Error, ..d }.with_note("warnings are errors because of `--deny-warnings`"))
+            .collect());
+    }
+    Ok(CompiledArtifact { ir, warnings })
+}
+
+/// Reformats `code` in the canonical style, keeping its comments.
+///
+/// Code with syntax errors is left alone and the errors are returned.
+pub fn format(code: &str, options: &PrintOptions) -> Result<String, Vec<Diagnostic>> {
+    let program = parse(code)?;
+    Ok(format_program(&program, code, &scan_comments(code), options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_ok() {
+        let artifact = compile("int a;\nvoid main() { a = 1; }", &LintConfig::default()).unwrap();
+        assert_eq!(artifact.ir.root.field_decls.len(), 1);
+        assert_eq!(artifact.ir.root.method_decls.len(), 1);
+    }
+
+    #[test]
+    fn test_compile_syntax_error() {
+        let errors = compile("void main() {", &LintConfig::default()).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "unexpected end of file");
+    }
+
+    #[test]
+    fn test_format_keeps_comments() {
+        let code = "// header\nint a,b; // two fields\n/* main */ void main( ) {\n\n  a=1;   /* set */\n\n\n  // done\n  b = 2;\n  // end\n}\n// trailer";
+        let expected = "\
+// header
+int a, b; // two fields
+
+/* main */
+void main() {
+    a = 1; /* set */
+
+    // done
+    b = 2;
+    // end
+}
+// trailer
+";
+        let formatted = format(code, &PrintOptions::default()).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format(&formatted, &PrintOptions::default()).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_keeps_comments_in_place() {
+        let code = "int a /* first */, b;\nvoid main( /* no args */ ) {\n  a = 1 /* mid */ + 2;\n  a = (1 /* in */) * f( /* none */ );\n  if (a /* cond */ > 0) /* then */ {\n    a = a // why\n      + 1 /* end */;\n  }\n}\n";
+        let expected = "\
+int a /* first */, b;
+
+void main(/* no args */) {
+    a = 1 /* mid */ + 2;
+    a = 1 /* in */ * f(/* none */);
+    if (a /* cond */ > 0) /* then */ {
+        a = a + 1 /* end */; // why
+    }
+}
+";
+        let formatted = format(code, &PrintOptions::default()).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format(&formatted, &PrintOptions::default()).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_one_decl_per_line() {
+        let options = PrintOptions { one_decl_per_line: true, ..PrintOptions::default() };
+        let formatted = format("int a, b[2];\nvoid main() { bool c, d; }", &options).unwrap();
+        assert_eq!(formatted, "int a;\nint b[2];\n\nvoid main() {\n    bool c;\n    bool d;\n}\n");
+    }
+
+    #[test]
+    fn test_compile_warnings() {
+        let code = "int f(int x) { if (x > 0) { return x; } }\nvoid main() { int y; f(1); }";
+        let artifact = compile(code, &LintConfig::default()).unwrap();
+        let messages: Vec<&str> = artifact.warnings.iter().map(|w| w.message.as_str()).collect();
+        assert_eq!(messages, vec!["`f` may reach its end without returning a value", "variable `y` is never read"]);
+        assert!(artifact.warnings.iter().all(|w| w.severity == Severity::Warning));
+        assert!(artifact.ir[artifact.ir.root.method_decls[0]].falls_off);
+
+        let mut lints = LintConfig::default();
+        lints.allow("unused-variable".parse().unwrap());
+        assert_eq!(compile(code, &lints).unwrap().warnings.len(), 1);
+
+        lints.deny_warnings = true;
+        let errors = compile(code, &lints).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_compile_semantic_errors() {
+        let errors = compile("void main() { a = 1; b = 2; }", &LintConfig::default()).unwrap_err();
+        assert!(errors.iter().all(|e| e.message.starts_with("cannot find")));
+        assert!(errors[0].span().is_some());
+    }
 }
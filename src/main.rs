@@ -1,82 +1,315 @@
-use decafc::compile;
+use std::env;
+use std::fs;
+use std::io::{ self, Write };
+use std::process;
+use std::thread;
 
-fn main() {
-    let code = r#"
-        int A[100];
-        int length;
-
-        int partition(int p, int r) {
-            int x, i, j, t, a, z;
-
-            x = A[p];
-            i = p - 1;
-            j = r + 1;
-
-            for (z = 0; z < length * length; z++) {
-                j = j - 1;
-                for (a = 0; a < length; a++) {
-                    if (A[j] <= x) {
-                        break;
-                    }
-                    j = j - 1;
-                }
+use decafc::ast::printer::PrintOptions;
+use decafc::codegen::CodegenOptions;
+use decafc::diagnostic::Diagnostic;
+use decafc::interpreter::{ CalloutTable, Interpreter };
+use decafc::scanner::dump_tokens;
+use decafc::semantic_analyzer::lint::LintConfig;
+use decafc::{ codegen, compile, format, parse, tac, Emit, Target };
 
-                for (a = i + 1; a < length; a++) {
-                    if (A[a] >= x) {
-                        i = a;
-                        break;
-                    }
-                }
+const USAGE: &str = "\
+usage: decafc [--target scan|parse|inter|assembly] [--emit cfg-dot] [-o <file>] [-W <lint>] [-A <lint>] [--deny-warnings] [--no-bounds-check] <file.dcf>
+       decafc fmt [--check] [--indent <n>] [--one-decl-per-line] <file.dcf>...
+       decafc run <file.dcf>";
+
+struct Options {
+    target: Target,
+    emit: Option<Emit>,
+    output: Option<String>,
+    lints: LintConfig,
+    codegen: CodegenOptions,
+    input: String,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut target = Target::Inter;
+    let mut emit = None;
+    let mut output = None;
+    let mut lints = LintConfig::default();
+    let mut codegen = CodegenOptions::default();
+    let mut input = None;
 
-                if (i < j) {
-                    t = A[i];
-                    A[i] = A[j];
-                    A[j] = t;
-                } else {
-                    return j;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-t" | "--target" => {
+                let value = iter.next().ok_or("missing value for `--target`")?;
+                target = value.parse()?;
+            }
+            "--emit" => {
+                let value = iter.next().ok_or("missing value for `--emit`")?;
+                emit = Some(value.parse()?);
+            }
+            "-o" | "--output" => {
+                let value = iter.next().ok_or("missing value for `-o`")?;
+                output = Some(value.clone());
+            }
+            "-W" => {
+                let value = iter.next().ok_or("missing value for `-W`")?;
+                lints.warn(value.parse()?);
+            }
+            "-A" => {
+                let value = iter.next().ok_or("missing value for `-A`")?;
+                lints.allow(value.parse()?);
+            }
+            "--deny-warnings" => lints.deny_warnings = true,
+            "--no-bounds-check" => codegen.bounds_check = false,
+            _ if arg.starts_with("-W") => lints.warn(arg["-W".len()..].parse()?),
+            _ if arg.starts_with("-A") => lints.allow(arg["-A".len()..].parse()?),
+            _ if arg.starts_with("--target=") => {
+                target = arg["--target=".len()..].parse()?;
+            }
+            _ if arg.starts_with("--emit=") => {
+                emit = Some(arg["--emit=".len()..].parse()?);
+            }
+            _ if arg.starts_with('-') => {
+                return Err(format!("unknown option `{}`", arg));
+            }
+            _ => {
+                if input.is_some() {
+                    return Err("more than one input file given".to_string());
                 }
+                input = Some(arg.clone());
             }
-            return -1;
         }
+    }
+
+    if emit.is_some() && target != Target::Inter {
+        return Err("`--emit` only applies to `--target inter`".to_string());
+    }
+    if !codegen.bounds_check && target != Target::Assembly {
+        return Err("`--no-bounds-check` only applies to `--target assembly`".to_string());
+    }
+    match input {
+        Some(input) => Ok(Options { target, emit, output, lints, codegen, input }),
+        None => Err("no input file given".to_string()),
+    }
+}
+
+struct FmtOptions {
+    check: bool,
+    print: PrintOptions,
+    inputs: Vec<String>,
+}
 
-        void quicksort(int p, int r) {
-            int q;
+fn parse_fmt_args(args: &[String]) -> Result<FmtOptions, String> {
+    let mut check = false;
+    let mut print = PrintOptions::default();
+    let mut inputs = Vec::new();
 
-            if (p < r) {
-                q = partition(p, r);
-                quicksort(p, q);
-                quicksort(q+1, r);
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--one-decl-per-line" => print.one_decl_per_line = true,
+            "--indent" => {
+                let value = iter.next().ok_or("missing value for `--indent`")?;
+                print.indent = value.parse().map_err(|_| format!("invalid indentation `{}`", value))?;
             }
+            _ if arg.starts_with('-') => {
+                return Err(format!("unknown option `{}`", arg));
+            }
+            _ => inputs.push(arg.clone()),
         }
+    }
 
-        void main() {
-            int temp, i;
-
-            length = 10; // adjust for sort length
+    if inputs.is_empty() {
+        return Err("no input file given".to_string());
+    }
+    Ok(FmtOptions { check, print, inputs })
+}
 
-            callout("printf", "creating random array of %d elements\n", length);
+fn render(diagnostics: Vec<Diagnostic>, file_name: &str, code: &str) -> String {
+    diagnostics
+        .iter()
+        .map(|d| d.render(file_name, code))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
 
-            callout("srandom", 17);
+fn run(options: &Options) -> Result<(), String> {
+    let code = fs::read_to_string(&options.input)
+        .map_err(|e| format!("cannot read `{}`: {}", options.input, e))?;
 
-            for (i = 0; i < length; i++) {
-                temp = callout("random");
-                A[i] = temp;
+    // the token listing is output even when it has errors in it
+    let mut scan_errors = 0;
+    let output = match options.target {
+        Target::Scan => {
+            let (listing, errors) = dump_tokens(&code, &options.input);
+            scan_errors = errors;
+            Ok(listing)
+        }
+        Target::Parse => parse(&code).map(|program| format!("{:#?}\n", program)),
+        Target::Inter => compile(&code, &options.lints).map(|artifact| {
+            if !artifact.warnings.is_empty() {
+                eprintln!("{}", render(artifact.warnings, &options.input, &code));
+            }
+            let program = tac::lower(&artifact.ir);
+            match options.emit {
+                Some(Emit::CfgDot) => tac::cfg::to_dot(&program),
+                None => program.to_string(),
             }
+        }),
+        Target::Assembly => compile(&code, &options.lints).map(|artifact| {
+            if !artifact.warnings.is_empty() {
+                eprintln!("{}", render(artifact.warnings, &options.input, &code));
+            }
+            codegen::emit_program(&tac::lower(&artifact.ir), &options.codegen)
+        }),
+    };
+    let output = output.map_err(|diagnostics| render(diagnostics, &options.input, &code))?;
+
+    match &options.output {
+        Some(path) => fs::write(path, output).map_err(|e| format!("cannot write `{}`: {}", path, e))?,
+        None => match io::stdout().write_all(output.as_bytes()) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(format!("cannot write output: {}", e)),
+            _ => {}
+        },
+    }
+    match scan_errors {
+        0 => Ok(()),
+        1 => Err(format!("`{}` has 1 lexical error", options.input)),
+        n => Err(format!("`{}` has {} lexical errors", options.input, n)),
+    }
+}
 
-            callout("printf", "\nbefore sort:\n");
-            for (i = 0; i < length; i++) {
-                callout ("printf", "%d\n", A[i]);
+// Formats every input in place, or with `--check` only lists the ones that
+// would change. Returns whether all inputs were already formatted.
+fn run_fmt(options: &FmtOptions) -> Result<bool, String> {
+    let mut errors = Vec::new();
+    let mut formatted = true;
+    for input in &options.inputs {
+        let code = fs::read_to_string(input).map_err(|e| format!("cannot read `{}`: {}", input, e))?;
+        let output = match format(&code, &options.print) {
+            Ok(output) => output,
+            Err(diagnostics) => {
+                errors.push(render(diagnostics, input, &code));
+                continue;
             }
+        };
+        if output == code {
+            continue;
+        }
+        formatted = false;
+        if options.check {
+            println!("{} is not formatted", input);
+        } else {
+            fs::write(input, output).map_err(|e| format!("cannot write `{}`: {}", input, e))?;
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(formatted)
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+// Standard output for interpreted programs, which like `run` stops writing
+// without an error once the reader goes away.
+struct Stdout(io::Stdout);
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.0.write(buf) {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(buf.len()),
+            result => result,
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.0.flush() {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            result => result,
+        }
+    }
+}
+
+// Interprets the program in `input`, and returns its exit status.
+fn run_interpreter(input: &str) -> Result<i32, String> {
+    let code = fs::read_to_string(input).map_err(|e| format!("cannot read `{}`: {}", input, e))?;
+    let artifact = compile(&code, &LintConfig::default()).map_err(|diagnostics| render(diagnostics, input, &code))?;
+    if !artifact.warnings.is_empty() {
+        eprintln!("{}", render(artifact.warnings, input, &code));
+    }
+
+    // every nested call of the program is a few nested calls of the
+    // interpreter
+    let ir = artifact.ir;
+    let result = thread::Builder::new()
+        .stack_size(1 << 30)
+        .spawn(move || {
+            let mut interpreter = Interpreter::new(&ir, CalloutTable::default(), Stdout(io::stdout()));
+            interpreter.run()
+        })
+        .map_err(|e| format!("cannot start the interpreter: {}", e))?
+        .join()
+        .map_err(|panic| {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            format!("the interpreter crashed: {}", message)
+        })?;
+    match result {
+        Ok(()) => Ok(0),
+        Err(e) => {
+            eprintln!("{}", render(vec![e.to_diagnostic()], input, &code));
+            Ok(e.exit_code())
+        }
+    }
+}
+
+fn usage_error(e: String) -> ! {
+    eprintln!("decafc: {}", e);
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
 
-            quicksort (0, length - 1);
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+
+    if args.first().map(String::as_str) == Some("fmt") {
+        let options = parse_fmt_args(&args[1..]).unwrap_or_else(|e| usage_error(e));
+        match run_fmt(&options) {
+            Ok(formatted) if !formatted && options.check => process::exit(1),
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
 
-            callout("printf", "\nafter sort\n");
-            for (i = 0; i < length; i++) {
-                callout ("printf", "%d\n", A[i]);
+    if args.first().map(String::as_str) == Some("run") {
+        let input = match &args[1..] {
+            [input] if !input.starts_with('-') => input,
+            _ => usage_error("`run` takes one input file".to_string()),
+        };
+        match run_interpreter(input) {
+            Ok(status) => process::exit(status),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
             }
         }
-    "#;
+    }
 
-    let code = String::from(code);
-    compile(&code);
+    let options = parse_args(&args).unwrap_or_else(|e| usage_error(e));
+    if let Err(e) = run(&options) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
 }
@@ -1,11 +1,101 @@
+use lalrpop_util::ParseError;
+
+use crate::ast::{ Program, Span };
 use crate::decaf::TProgramParser;
+use crate::diagnostic::Diagnostic;
+use crate::scanner::{ LexError, Scanner, SpannedToken, Token };
 pub type DecafParser = TProgramParser;
 
+pub type DecafParseError = ParseError<usize, Token, LexError>;
+
+pub fn parse(code: &str) -> Result<Program, Vec<Diagnostic>> {
+    match parse_recovering(code) {
+        (Some(program), errors) if errors.is_empty() => Ok(program),
+        (_, errors) => Err(errors),
+    }
+}
+
+/// Parses as much of `code` as possible, reporting every syntax error.
+///
+/// Statements that fail to parse become `Statement_::Error`, so the returned
+/// program can still be walked. It is `None` only if the parser could not
+/// recover at all, e.g. at an unexpected end of file.
+pub fn parse_recovering(code: &str) -> (Option<Program>, Vec<Diagnostic>) {
+    let mut lex_errors = Vec::new();
+    let mut recovered = Vec::new();
+    let tokens = Scanner::new(code).map(|t| invalid_token(t, &mut lex_errors));
+    let result = DecafParser::new().parse(&mut recovered, tokens);
+
+    let mut errors: Vec<(Span, Diagnostic)> = lex_errors.iter().map(|e| (e.span, e.to_diagnostic())).collect();
+    let mut report = |e: &DecafParseError| {
+        if !at_invalid_token(e) {
+            errors.push((parse_error_span(e), parse_error_to_diagnostic(e)));
+        }
+    };
+    for r in &recovered {
+        report(&r.error);
+    }
+    let program = match result {
+        Ok(program) => Some(program),
+        Err(e) => {
+            report(&e);
+            None
+        }
+    };
+    errors.sort_by_key(|(span, _)| span.start);
+    (program, errors.into_iter().map(|(_, d)| d).collect())
+}
+
+// lex errors are reported separately, and the parser sees an invalid token
+// in their place that it recovers from like from a syntax error
+fn invalid_token(token: Result<SpannedToken, LexError>, errors: &mut Vec<LexError>) -> Result<SpannedToken, LexError> {
+    match token {
+        Ok(t) => Ok(t),
+        Err(e) => {
+            let t = (e.span.start, Token::Invalid, e.span.end);
+            errors.push(e);
+            Ok(t)
+        }
+    }
+}
+
+// whether `e` is about an invalid token, whose lex error is already reported
+fn at_invalid_token(e: &DecafParseError) -> bool {
+    matches!(e, ParseError::UnrecognizedToken { token: (_, Token::Invalid, _), .. } | ParseError::ExtraToken { token: (_, Token::Invalid, _) })
+}
+
+pub fn parse_error_to_diagnostic(e: &DecafParseError) -> Diagnostic {
+    let expected_note = |expected: &Vec<String>| format!("expected one of {}", expected.join(", "));
+    let diagnostic = match e {
+        ParseError::InvalidToken { .. } => Diagnostic::error("invalid token"),
+        ParseError::UnrecognizedEOF { expected, .. } => Diagnostic::error("unexpected end of file")
+            .with_note(expected_note(expected)),
+        ParseError::UnrecognizedToken { token: (_, t, _), expected } => Diagnostic::error(format!("unexpected token `{}`", t))
+            .with_note(expected_note(expected)),
+        ParseError::ExtraToken { token: (_, t, _) } => Diagnostic::error(format!("extra token `{}`", t)),
+        ParseError::User { error } => return error.to_diagnostic(),
+    };
+    diagnostic.with_label(parse_error_span(e), "")
+}
+
+// source range a parse error points at
+pub fn parse_error_span(e: &DecafParseError) -> Span {
+    match e {
+        ParseError::InvalidToken { location } => Span::new(*location, *location),
+        ParseError::UnrecognizedEOF { location, .. } => Span::new(*location, *location),
+        ParseError::UnrecognizedToken { token: (l, _, r), .. } => Span::new(*l, *r),
+        ParseError::ExtraToken { token: (l, _, r) } => Span::new(*l, *r),
+        ParseError::User { error } => error.span,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs::read_to_string;
     use std::path::PathBuf;
-    use super::DecafParser;
+    use super::{ parse, parse_recovering };
+    use crate::ast::*;
+    use crate::span::LineIndex;
     use crate::test_util::get_current_dir;
 
 macro_rules! test_parser_illegal {
@@ -15,7 +105,7 @@ macro_rules! test_parser_illegal {
             let path = get_current_dir();
             let path: PathBuf = [&path, "src", "parser", "testcases", "illegal", $filename].iter().collect();
             let s = read_to_string(&path).unwrap();
-            let program = DecafParser::new().parse(&s);
+            let program = parse(&s);
             assert!(program.is_err());
         }
     };
@@ -28,7 +118,7 @@ macro_rules! test_parser_legal {
             let path = get_current_dir();
             let path: PathBuf = [&path, "src", "parser", "testcases", "legal", $filename].iter().collect();
             let s = read_to_string(&path).unwrap();
-            let program = DecafParser::new().parse(&s);
+            let program = parse(&s);
             assert!(program.is_ok());
         }
     };
@@ -36,7 +126,164 @@ macro_rules! test_parser_legal {
 
     #[test]
     fn test_empty() {
-        assert!(DecafParser::new().parse("").is_ok());
+        assert!(parse("").is_ok());
+    }
+
+    #[test]
+    fn test_spans() {
+        let code = "int a[10];\nvoid main() {\n    a[1] = 2 + len(a);\n    break;\n}";
+        let program = parse(code).unwrap();
+        let text = |span: crate::ast::Span| &code[span.start..span.end];
+
+        assert_eq!(text(program.field_decls[0].span), "int a[10];");
+        assert_eq!(text(program.field_decls[0].field_ids[0].span), "a[10]");
+        let method = &program.method_decls[0];
+        assert_eq!(text(method.span), &code[11..]);
+        assert_eq!(text(method.id.span), "main");
+        assert_eq!(text(method.block.statements[1].span), "break;");
+
+        let assign = match &method.block.statements[0].node {
+            Statement_::Assign(a) => a,
+            _ => panic!("expected an assignment"),
+        };
+        assert_eq!(text(method.block.statements[0].span), "a[1] = 2 + len(a);");
+        assert!(matches!(assign.dst, Location::IdExpr(_)));
+        assert_eq!(text(assign.dst.span()), "a[1]");
+        let expr = match &assign.assign_expr {
+            crate::ast::AssignExpr::AssignOpExpr(e) => &e.expr,
+            _ => panic!("expected `=`"),
+        };
+        assert_eq!(text(expr.span), "2 + len(a)");
+        match &expr.node {
+            Expr_::Binary(b) => {
+                assert_eq!(text(b.lhs.span), "2");
+                assert_eq!(text(b.rhs.span), "len(a)");
+            }
+            _ => panic!("expected a binary expression"),
+        }
+    }
+
+    // renders an expression as an s-expression, e.g. `(+ a (* b c))`
+    fn sexp(e: &Expr) -> String {
+        match &e.node {
+            Expr_::Location(Location::Id(id)) => id.node.clone(),
+            Expr_::Location(Location::IdExpr(e)) => format!("([] {} {})", e.id.node, sexp(&e.array_len)),
+            Expr_::MethodCall(_) => "call".to_string(),
+            Expr_::Literal(Literal::IntLiteral(i)) => i.text().to_string(),
+            Expr_::Literal(Literal::CharLiteral(c)) => format!("{:?}", c),
+            Expr_::Literal(Literal::BoolLiteral(b)) => format!("{:?}", b).to_lowercase(),
+            Expr_::LenId(id) => format!("(len {})", id.node),
+            Expr_::Unary(u) => {
+                let op = match u.op {
+                    UnaryOp::NegInt => "-",
+                    UnaryOp::NegBool => "!",
+                };
+                format!("({} {})", op, sexp(&u.expr))
+            }
+            Expr_::Binary(b) => {
+                let op = printer::binary_op(&b.op);
+                format!("({} {} {})", op, sexp(&b.lhs), sexp(&b.rhs))
+            }
+        }
+    }
+
+    fn parse_expr(expr: &str) -> String {
+        let program = parse(&format!("void main() {{ x = {}; }}", expr)).unwrap();
+        match &program.method_decls[0].block.statements[0].node {
+            Statement_::Assign(Assign { assign_expr: AssignExpr::AssignOpExpr(a), .. }) => sexp(&a.expr),
+            _ => panic!("expected an assignment"),
+        }
+    }
+
+    #[test]
+    fn test_operators() {
+        let cases = [
+            ("a + b", "(+ a b)"),
+            ("a - b", "(- a b)"),
+            ("a * b", "(* a b)"),
+            ("a / b", "(/ a b)"),
+            ("a % b", "(% a b)"),
+            ("a < b", "(< a b)"),
+            ("a <= b", "(<= a b)"),
+            ("a > b", "(> a b)"),
+            ("a >= b", "(>= a b)"),
+            ("a == b", "(== a b)"),
+            ("a != b", "(!= a b)"),
+            ("a && b", "(&& a b)"),
+            ("a || b", "(|| a b)"),
+            ("!a", "(! a)"),
+            ("-a", "(- a)"),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(parse_expr(expr), expected, "parsing `{}`", expr);
+        }
+    }
+
+    #[test]
+    fn test_precedence() {
+        let cases = [
+            ("a + b * c", "(+ a (* b c))"),
+            ("a * b + c", "(+ (* a b) c)"),
+            ("a - b - c", "(- (- a b) c)"),
+            ("a / b % c", "(% (/ a b) c)"),
+            ("(a + b) * c", "(* (+ a b) c)"),
+            ("a + b < c * d", "(< (+ a b) (* c d))"),
+            ("a < b == c >= d", "(== (< a b) (>= c d))"),
+            ("a == b && c != d", "(&& (== a b) (!= c d))"),
+            ("a || b && c", "(|| a (&& b c))"),
+            ("a && b || c && d", "(|| (&& a b) (&& c d))"),
+            ("!a && b", "(&& (! a) b)"),
+            ("!!a", "(! (! a))"),
+            ("-a * b", "(* (- a) b)"),
+            ("a - -b", "(- a (- b))"),
+            ("-a[i + 1]", "(- ([] a (+ i 1)))"),
+            ("len(a) - 1 <= 0x1F", "(<= (- (len a) 1) 0x1F)"),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(parse_expr(expr), expected, "parsing `{}`", expr);
+        }
+    }
+
+    #[test]
+    fn test_error_recovery() {
+        let path: PathBuf = [&get_current_dir(), "src", "parser", "testcases", "illegal", "illegal-22"].iter().collect();
+        let s = read_to_string(&path).unwrap();
+        let (program, errors) = parse_recovering(&s);
+        let index = LineIndex::new(&s);
+        let lines: Vec<usize> = errors.iter().map(|e| index.line_col(e.span().unwrap().start).line).collect();
+        // `#` is reported once, without a second error at the `2` after it
+        assert_eq!(lines, vec![1, 2, 3, 4, 5, 6, 7]);
+
+        let program = program.unwrap();
+        assert!(program.field_decls.iter().all(|f| f.field_ids.is_empty()));
+        let foo = &program.method_decls[0];
+        assert!(foo.args.is_empty());
+        let broken = foo.block.statements.iter().filter(|s| matches!(s.node, Statement_::Error)).count();
+        assert_eq!((broken, foo.block.statements.len()), (4, 5));
+        assert_eq!(program.method_decls[1].id.node, "main");
+    }
+
+    #[test]
+    fn test_unrecoverable_error() {
+        let (program, errors) = parse_recovering("void main() {\n    a = ;\n");
+        assert!(program.is_none());
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, vec!["unexpected token `;`", "unexpected end of file"]);
+    }
+
+    #[test]
+    fn test_lex_error_recovery() {
+        let (program, errors) = parse_recovering("void main() {\n    int x;\n    x = 'ab';\n}\n");
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, vec!["a character literal must contain exactly one character"]);
+        let main = &program.unwrap().method_decls[0];
+        assert!(matches!(main.block.statements[0].node, Statement_::Error));
+
+        // a real syntax error after one is still reported, with the usual note
+        let (_, errors) = parse_recovering("void main() {\n    x = 1 + @ * 2;\n    x = ;\n}\n");
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, vec!["unexpected character '@'", "unexpected token `;`"]);
+        assert!(!errors[1].notes[0].contains("\"+\""));
     }
 
     test_parser_legal!(test_legal_01, "legal-01");
@@ -57,6 +304,7 @@ macro_rules! test_parser_legal {
     test_parser_legal!(test_legal_16, "legal-16");
     test_parser_legal!(test_legal_17, "legal-17");
     test_parser_legal!(test_legal_18, "legal-18");
+    test_parser_legal!(test_legal_19, "legal-19");
 
     test_parser_illegal!(test_illegal_01, "illegal-01");
     test_parser_illegal!(test_illegal_02, "illegal-02");
@@ -78,4 +326,6 @@ macro_rules! test_parser_legal {
     test_parser_illegal!(test_illegal_18, "illegal-18");
     test_parser_illegal!(test_illegal_19, "illegal-19");
     test_parser_illegal!(test_illegal_20, "illegal-20");
+    test_parser_illegal!(test_illegal_21, "illegal-21");
+    test_parser_illegal!(test_illegal_22, "illegal-22");
 }
\ No newline at end of file
@@ -0,0 +1,610 @@
+use std::fmt;
+
+use crate::ast::Span;
+use crate::diagnostic::Diagnostic;
+use crate::span::LineIndex;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    Id(String),
+    DecimalLiteral(String),
+    HexLiteral(String),
+    CharLiteral(char),
+    StringLiteral(String),
+    // keywords
+    Import,
+    Int,
+    Bool,
+    Void,
+    If,
+    Else,
+    For,
+    While,
+    Return,
+    Break,
+    Continue,
+    Callout,
+    Len,
+    True,
+    False,
+    // punctuation
+    LBrace,
+    RBrace,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Semicolon,
+    Comma,
+    // operators
+    Assign,
+    AddAssign,
+    SubAssign,
+    Increment,
+    Decrement,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Not,
+    Less,
+    Greater,
+    LessEq,
+    GreaterEq,
+    Eq,
+    NotEq,
+    And,
+    Or,
+    /// stands in for a malformed token, so that the parser can recover past
+    /// it; never produced by the scanner itself
+    Invalid,
+}
+
+impl Token {
+    fn keyword(s: &str) -> Option<Token> {
+        let t = match s {
+            "import" => Token::Import,
+            "int" => Token::Int,
+            "bool" => Token::Bool,
+            "void" => Token::Void,
+            "if" => Token::If,
+            "else" => Token::Else,
+            "for" => Token::For,
+            "while" => Token::While,
+            "return" => Token::Return,
+            "break" => Token::Break,
+            "continue" => Token::Continue,
+            "callout" => Token::Callout,
+            "len" => Token::Len,
+            "true" => Token::True,
+            "false" => Token::False,
+            _ => return None,
+        };
+        Some(t)
+    }
+
+    /// The token class printed by the 6.035 scanner output format, or `None`
+    /// for keywords and punctuation, which are printed as they are.
+    pub fn class(&self) -> Option<&'static str> {
+        match self {
+            Token::Id(_) => Some("IDENTIFIER"),
+            Token::DecimalLiteral(_) | Token::HexLiteral(_) => Some("INTLITERAL"),
+            Token::CharLiteral(_) => Some("CHARLITERAL"),
+            Token::StringLiteral(_) => Some("STRINGLITERAL"),
+            Token::True | Token::False => Some("BOOLEANLITERAL"),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Token::Id(s) | Token::DecimalLiteral(s) | Token::HexLiteral(s) => return write!(f, "{}", s),
+            Token::CharLiteral(c) => return write!(f, "'{}'", escape_char(*c)),
+            Token::StringLiteral(s) => return write!(f, "\"{}\"", escape_string(s)),
+            Token::Import => "import",
+            Token::Int => "int",
+            Token::Bool => "bool",
+            Token::Void => "void",
+            Token::If => "if",
+            Token::Else => "else",
+            Token::For => "for",
+            Token::While => "while",
+            Token::Return => "return",
+            Token::Break => "break",
+            Token::Continue => "continue",
+            Token::Callout => "callout",
+            Token::Len => "len",
+            Token::True => "true",
+            Token::False => "false",
+            Token::LBrace => "{",
+            Token::RBrace => "}",
+            Token::LParen => "(",
+            Token::RParen => ")",
+            Token::LBracket => "[",
+            Token::RBracket => "]",
+            Token::Semicolon => ";",
+            Token::Comma => ",",
+            Token::Assign => "=",
+            Token::AddAssign => "+=",
+            Token::SubAssign => "-=",
+            Token::Increment => "++",
+            Token::Decrement => "--",
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::Star => "*",
+            Token::Slash => "/",
+            Token::Percent => "%",
+            Token::Not => "!",
+            Token::Less => "<",
+            Token::Greater => ">",
+            Token::LessEq => "<=",
+            Token::GreaterEq => ">=",
+            Token::Eq => "==",
+            Token::NotEq => "!=",
+            Token::And => "&&",
+            Token::Or => "||",
+            Token::Invalid => "<invalid>",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LexErrorKind {
+    UnexpectedChar(char),
+    UnterminatedChar,
+    InvalidChar,
+    UnterminatedString,
+    InvalidEscape(char),
+    UnescapedQuote(char),
+    NonPrintableChar(char),
+    MalformedHex,
+    UnterminatedComment,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub span: Span,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            LexErrorKind::UnexpectedChar(c) => write!(f, "unexpected character {:?}", c),
+            LexErrorKind::UnterminatedChar => write!(f, "unterminated character literal"),
+            LexErrorKind::InvalidChar => write!(f, "a character literal must contain exactly one character"),
+            LexErrorKind::UnterminatedString => write!(f, "unterminated string literal"),
+            LexErrorKind::InvalidEscape(c) => write!(f, "invalid escape sequence `\\{}`", c.escape_default()),
+            LexErrorKind::UnescapedQuote(c) => write!(f, "`{}` must be escaped as `\\{}` in a literal", c, c),
+            LexErrorKind::NonPrintableChar(c) => write!(f, "character {:?} is not allowed in a literal", c),
+            LexErrorKind::MalformedHex => write!(f, "hex literal must have at least one digit after `0x`"),
+            LexErrorKind::UnterminatedComment => write!(f, "unterminated block comment"),
+        }
+    }
+}
+
+impl LexError {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::error(self.to_string()).with_label(self.span, "")
+    }
+}
+
+pub type SpannedToken = (usize, Token, usize);
+
+/// A comment skipped by the scanner, kept so that tools like the formatter
+/// can put it back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Comment {
+    pub span: Span,
+    pub block: bool,
+}
+
+/// Splits source text into tokens, skipping whitespace and comments.
+///
+/// Malformed tokens are reported as errors and skipped, so iteration can go
+/// on to report every error in the file.
+pub struct Scanner<'a> {
+    src: &'a str,
+    pos: usize,
+    comments: Vec<Comment>,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(src: &'a str) -> Self {
+        Self { src, pos: 0, comments: Vec::new() }
+    }
+
+    /// Comments skipped so far, in source order.
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.pos).copied()
+    }
+
+    fn peek_at(&self, n: usize) -> Option<u8> {
+        self.src.as_bytes().get(self.pos + n).copied()
+    }
+
+    fn error(&self, kind: LexErrorKind, start: usize) -> LexError {
+        LexError { kind, span: Span::new(start, self.pos) }
+    }
+
+    fn skip_whitespace_and_comments(&mut self) -> Result<(), LexError> {
+        loop {
+            match self.peek() {
+                Some(b' ' | b'\t' | b'\n' | b'\r' | b'\x0c') => self.pos += 1,
+                Some(b'/') if self.peek_at(1) == Some(b'/') => {
+                    let start = self.pos;
+                    while !matches!(self.peek(), None | Some(b'\n')) {
+                        self.pos += 1;
+                    }
+                    let end = if self.src[..self.pos].ends_with('\r') { self.pos - 1 } else { self.pos };
+                    self.comments.push(Comment { span: Span::new(start, end), block: false });
+                }
+                Some(b'/') if self.peek_at(1) == Some(b'*') => self.skip_block_comment()?,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Block comments nest, so `/* a /* b */ c */` is a single comment.
+    fn skip_block_comment(&mut self) -> Result<(), LexError> {
+        let start = self.pos;
+        let mut depth = 0;
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some(b'/'), Some(b'*')) => {
+                    depth += 1;
+                    self.pos += 2;
+                }
+                (Some(b'*'), Some(b'/')) => {
+                    depth -= 1;
+                    self.pos += 2;
+                    if depth == 0 {
+                        self.comments.push(Comment { span: Span::new(start, self.pos), block: true });
+                        return Ok(());
+                    }
+                }
+                (Some(_), _) => self.pos += 1,
+                (None, _) => {
+                    // report the opening delimiter rather than the whole rest of the file
+                    return Err(LexError { kind: LexErrorKind::UnterminatedComment, span: Span::new(start, start + 2) });
+                }
+            }
+        }
+    }
+
+    fn scan_word(&mut self) -> Token {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == b'_') {
+            self.pos += 1;
+        }
+        let word = &self.src[start..self.pos];
+        Token::keyword(word).unwrap_or_else(|| Token::Id(word.to_string()))
+    }
+
+    fn scan_number(&mut self) -> Result<Token, LexError> {
+        let start = self.pos;
+        if self.peek() == Some(b'0') && self.peek_at(1) == Some(b'x') {
+            self.pos += 2;
+            while matches!(self.peek(), Some(c) if c.is_ascii_hexdigit()) {
+                self.pos += 1;
+            }
+            if self.pos == start + 2 {
+                return Err(self.error(LexErrorKind::MalformedHex, start));
+            }
+            return Ok(Token::HexLiteral(self.src[start..self.pos].to_string()));
+        }
+        while matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        Ok(Token::DecimalLiteral(self.src[start..self.pos].to_string()))
+    }
+
+    // one (possibly escaped) character of a char or string literal, with
+    // the cursor on its first byte
+    fn scan_literal_char(&mut self, quote: char) -> Result<char, LexError> {
+        let start = self.pos;
+        let c = self.src[self.pos..].chars().next().unwrap();
+        self.pos += c.len_utf8();
+        match c {
+            '\\' => {
+                let e = match self.src[self.pos..].chars().next() {
+                    None | Some('\n') => return Err(self.error(LexErrorKind::UnterminatedString, start)),
+                    Some(e) => e,
+                };
+                self.pos += e.len_utf8();
+                match e {
+                    '"' => Ok('"'),
+                    '\'' => Ok('\''),
+                    '\\' => Ok('\\'),
+                    't' => Ok('\t'),
+                    'n' => Ok('\n'),
+                    _ => Err(self.error(LexErrorKind::InvalidEscape(e), start)),
+                }
+            }
+            '"' | '\'' if c != quote => Err(self.error(LexErrorKind::UnescapedQuote(c), start)),
+            ' '..='~' => Ok(c),
+            _ => Err(self.error(LexErrorKind::NonPrintableChar(c), start)),
+        }
+    }
+
+    fn scan_char(&mut self) -> Result<Token, LexError> {
+        let start = self.pos;
+        self.pos += 1;
+        let c = match self.peek() {
+            None | Some(b'\n') => return Err(self.error(LexErrorKind::UnterminatedChar, start)),
+            Some(b'\'') => {
+                self.pos += 1;
+                return Err(self.error(LexErrorKind::InvalidChar, start));
+            }
+            Some(_) => self.scan_literal_char('\''),
+        };
+        if self.peek() != Some(b'\'') {
+            // skip to the closing quote, if it is on this line, so that the rest
+            // of a malformed literal is not scanned as more tokens
+            let rest = &self.src[self.pos..];
+            let line_end = rest.find('\n').unwrap_or(rest.len());
+            return match rest[..line_end].find('\'') {
+                Some(i) => {
+                    self.pos += i + 1;
+                    Err(self.error(LexErrorKind::InvalidChar, start))
+                }
+                None => Err(self.error(LexErrorKind::UnterminatedChar, start)),
+            };
+        }
+        self.pos += 1;
+        Ok(Token::CharLiteral(c?))
+    }
+
+    fn scan_string(&mut self) -> Result<Token, LexError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut s = String::new();
+        let mut error = None;
+        loop {
+            match self.peek() {
+                None | Some(b'\n') => return Err(self.error(LexErrorKind::UnterminatedString, start)),
+                Some(b'"') => break,
+                Some(_) => match self.scan_literal_char('"') {
+                    Ok(c) => s.push(c),
+                    // keep going to the closing quote, reporting the first bad character
+                    Err(e) => {
+                        error.get_or_insert(e);
+                    }
+                },
+            }
+        }
+        self.pos += 1;
+        match error {
+            Some(e) => Err(e),
+            None => Ok(Token::StringLiteral(s)),
+        }
+    }
+
+    fn scan_operator(&mut self) -> Result<Token, LexError> {
+        let start = self.pos;
+        let two = match (self.peek(), self.peek_at(1)) {
+            (Some(b'+'), Some(b'=')) => Some(Token::AddAssign),
+            (Some(b'-'), Some(b'=')) => Some(Token::SubAssign),
+            (Some(b'+'), Some(b'+')) => Some(Token::Increment),
+            (Some(b'-'), Some(b'-')) => Some(Token::Decrement),
+            (Some(b'<'), Some(b'=')) => Some(Token::LessEq),
+            (Some(b'>'), Some(b'=')) => Some(Token::GreaterEq),
+            (Some(b'='), Some(b'=')) => Some(Token::Eq),
+            (Some(b'!'), Some(b'=')) => Some(Token::NotEq),
+            (Some(b'&'), Some(b'&')) => Some(Token::And),
+            (Some(b'|'), Some(b'|')) => Some(Token::Or),
+            _ => None,
+        };
+        if let Some(t) = two {
+            self.pos += 2;
+            return Ok(t);
+        }
+
+        let c = self.src[self.pos..].chars().next().unwrap();
+        self.pos += c.len_utf8();
+        let t = match c {
+            '{' => Token::LBrace,
+            '}' => Token::RBrace,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            ';' => Token::Semicolon,
+            ',' => Token::Comma,
+            '=' => Token::Assign,
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' => Token::Star,
+            '/' => Token::Slash,
+            '%' => Token::Percent,
+            '!' => Token::Not,
+            '<' => Token::Less,
+            '>' => Token::Greater,
+            _ => return Err(self.error(LexErrorKind::UnexpectedChar(c), start)),
+        };
+        Ok(t)
+    }
+}
+
+impl<'a> Iterator for Scanner<'a> {
+    type Item = Result<SpannedToken, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(e) = self.skip_whitespace_and_comments() {
+            return Some(Err(e));
+        }
+        let start = self.pos;
+        let token = match self.peek()? {
+            c if c.is_ascii_alphabetic() || c == b'_' => Ok(self.scan_word()),
+            c if c.is_ascii_digit() => self.scan_number(),
+            b'\'' => self.scan_char(),
+            b'"' => self.scan_string(),
+            _ => self.scan_operator(),
+        };
+        Some(token.map(|t| (start, t, self.pos)))
+    }
+}
+
+/// Every comment in `src`, in source order.
+pub fn scan_comments(src: &str) -> Vec<Comment> {
+    let mut scanner = Scanner::new(src);
+    scanner.by_ref().for_each(drop);
+    scanner.comments
+}
+
+/// Escapes `c` the way it would be written in a Decaf literal.
+pub fn escape_char(c: char) -> String {
+    match c {
+        '"' => "\\\"".to_string(),
+        '\'' => "\\'".to_string(),
+        '\\' => "\\\\".to_string(),
+        '\t' => "\\t".to_string(),
+        '\n' => "\\n".to_string(),
+        _ => c.to_string(),
+    }
+}
+
+pub fn escape_string(s: &str) -> String {
+    s.chars().map(escape_char).collect()
+}
+
+/// Dumps the tokens of `src` in the 6.035 scanner output format, one token
+/// per line, e.g. `1 IDENTIFIER foo` or `3 CHARLITERAL 'a'`.
+///
+/// Like the reference scanner, errors are listed in place among the tokens,
+/// as `<file> line <line>:<column>: <message>`, and scanning goes on past
+/// them. Returns the listing and the number of errors in it.
+pub fn dump_tokens(src: &str, file_name: &str) -> (String, usize) {
+    let index = LineIndex::new(src);
+    let mut out = String::new();
+    let mut errors = 0;
+
+    for token in Scanner::new(src) {
+        match token {
+            Ok((l, t, r)) => {
+                let line = index.line_col(l).line;
+                match t.class() {
+                    Some(class) => out.push_str(&format!("{} {} {}\n", line, class, &src[l..r])),
+                    None => out.push_str(&format!("{} {}\n", line, &src[l..r])),
+                }
+            }
+            Err(e) => {
+                let pos = index.line_col(e.span.start);
+                out.push_str(&format!("{} line {}:{}: {}\n", file_name, pos.line, pos.col, e));
+                errors += 1;
+            }
+        }
+    }
+    (out, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+    use std::path::PathBuf;
+    use super::*;
+    use crate::test_util::get_current_dir;
+
+    macro_rules! test_scanner {
+        ( $testname:ident, $filename:expr ) => {
+            #[test]
+            fn $testname() {
+                let path = get_current_dir();
+                let dir: PathBuf = [&path, "src", "scanner", "testcases"].iter().collect();
+                let s = read_to_string(dir.join($filename)).unwrap();
+                let expected = read_to_string(dir.join(concat!($filename, ".out"))).unwrap();
+                let (actual, _) = dump_tokens(&s, $filename);
+                assert_eq!(actual, expected);
+            }
+        };
+    }
+
+    fn tokens(src: &str) -> Vec<Token> {
+        Scanner::new(src).map(|t| t.unwrap().1).collect()
+    }
+
+    #[test]
+    fn test_maximal_munch() {
+        assert_eq!(tokens("a+++b"), vec![
+            Token::Id("a".to_string()),
+            Token::Increment,
+            Token::Plus,
+            Token::Id("b".to_string()),
+        ]);
+        assert_eq!(tokens("x<=-1"), vec![
+            Token::Id("x".to_string()),
+            Token::LessEq,
+            Token::Minus,
+            Token::DecimalLiteral("1".to_string()),
+        ]);
+        assert_eq!(tokens("ifx if"), vec![Token::Id("ifx".to_string()), Token::If]);
+    }
+
+    #[test]
+    fn test_spans() {
+        let spans: Vec<(usize, usize)> = Scanner::new("int a; // x\n  a")
+            .map(|t| t.map(|(l, _, r)| (l, r)).unwrap())
+            .collect();
+        assert_eq!(spans, vec![(0, 3), (4, 5), (5, 6), (14, 15)]);
+    }
+
+    #[test]
+    fn test_comments() {
+        assert_eq!(tokens("a /* b /* c */ d */ e"), vec![Token::Id("a".to_string()), Token::Id("e".to_string())]);
+        assert_eq!(tokens("a/**/b/***/"), vec![Token::Id("a".to_string()), Token::Id("b".to_string())]);
+        assert_eq!(tokens("a // no newline at end of file"), vec![Token::Id("a".to_string())]);
+        assert_eq!(tokens("a /* // */ b"), vec![Token::Id("a".to_string()), Token::Id("b".to_string())]);
+    }
+
+    #[test]
+    fn test_comments_are_kept() {
+        let src = "a // one\r\n/* two /* three */ */ b //";
+        let comments: Vec<(&str, bool)> = scan_comments(src)
+            .iter()
+            .map(|c| (&src[c.span.start..c.span.end], c.block))
+            .collect();
+        assert_eq!(comments, vec![("// one", false), ("/* two /* three */ */", true), ("//", false)]);
+    }
+
+    #[test]
+    fn test_errors_are_skipped() {
+        let result: Vec<Result<Token, LexErrorKind>> = Scanner::new("a # b")
+            .map(|t| t.map(|(_, t, _)| t).map_err(|e| e.kind))
+            .collect();
+        assert_eq!(result, vec![
+            Ok(Token::Id("a".to_string())),
+            Err(LexErrorKind::UnexpectedChar('#')),
+            Ok(Token::Id("b".to_string())),
+        ]);
+    }
+
+    #[test]
+    fn test_escapes_are_decoded() {
+        assert_eq!(tokens(r#"'\n' '\'' "\"quoted\"\t\\" "a" "b""#), vec![
+            Token::CharLiteral('\n'),
+            Token::CharLiteral('\''),
+            Token::StringLiteral("\"quoted\"\t\\".to_string()),
+            Token::StringLiteral("a".to_string()),
+            Token::StringLiteral("b".to_string()),
+        ]);
+        assert_eq!(escape_string("say \"hi\"\n"), r#"say \"hi\"\n"#);
+    }
+
+    test_scanner!(test_scanner_ids, "ids");
+    test_scanner!(test_scanner_numbers, "numbers");
+    test_scanner!(test_scanner_ops, "ops");
+    test_scanner!(test_scanner_literals, "literals");
+    test_scanner!(test_scanner_bad_char, "bad-char");
+    test_scanner!(test_scanner_escapes, "escapes");
+    test_scanner!(test_scanner_bad_escapes, "bad-escapes");
+    test_scanner!(test_scanner_comments, "comments");
+    test_scanner!(test_scanner_unterminated_comment, "unterminated-comment");
+}
@@ -1,29 +1,50 @@
 use std::cell::RefCell;
-use std::rc::Rc;
 
-use crate::ast;
+use crate::ast::{ self, Span, Spanned };
 
 use super::env::{ EnvStack, EnvType };
 use super::ir;
 use super::errors::{ IRResult, SemanticCheckError };
 use super::pre_check::{ check_main };
 
+/// Builds the IR for a program, checking the semantic rules on the way.
+///
+/// Checking does not stop at the first error: a node that fails to check is
+/// still built, with `ir::Type::Error` standing in for whatever could not be
+/// worked out, and undeclared names are bound to a poisoned variable, so that
+/// every independent error is reported exactly once.
 pub struct SemanticAnalyzer {
     envs: RefCell<EnvStack>,
+    ir: RefCell<ir::IRRoot>,
+    errors: RefCell<Vec<SemanticCheckError>>,
+}
+
+impl Default for SemanticAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SemanticAnalyzer {
     pub fn new() -> Self {
         Self {
             envs: RefCell::new(EnvStack::new()),
+            ir: RefCell::new(ir::IRRoot::default()),
+            errors: RefCell::new(Vec::new()),
         }
     }
 
     pub fn create_ir(&self, p: ast::Program) -> IRResult<ir::IRRoot> {
-        if let Err(errors) = self.pre_check(&p) {
-            return Err(errors);
+        let pre_check = self.pre_check(&p);
+        let ir = self.construct_ir(p);
+        match (pre_check, ir) {
+            (Ok(()), ir) => ir,
+            (Err(errors), Ok(_)) => Err(errors),
+            (Err(mut errors), Err(e)) => {
+                errors.extend(e);
+                Err(errors)
+            }
         }
-        return self.construct_ir(p);
     }
 
     pub fn pre_check(&self, p: &ast::Program) -> IRResult<()> {
@@ -44,436 +65,420 @@ impl SemanticAnalyzer {
     }
 
     pub fn construct_ir(&self, p: ast::Program) -> IRResult<ir::IRRoot> {
-        self.envs.borrow_mut().push(EnvType::Global);
-        let mut errors = Vec::new();
-
-        let import_decls = self.get_ir_import_decls(p.import_decls);
-        let import_decls = match import_decls {
-            Err(e) => {
-                errors.extend(e);
-                None
-            },
-            Ok(imports) => Some(imports),
-        };
-
-        let field_decls = self.get_ir_field_decls(p.field_decls);
-        let field_decls = match field_decls {
-            Err(e) => {
-                errors.extend(e);
-                None
-            },
-            Ok(fields) => Some(fields),
-        };
-
-        let method_decls = self.get_ir_method_decls(p.method_decls);
-        let method_decls = match method_decls {
-            Err(e) => {
-                errors.extend(e);
-                None
-            },
-            Ok(methods) => Some(methods),
-        };
-
-        self.envs.borrow_mut().pop();
+        let root = self.with_scope(EnvType::Global, || ir::ProgramDecl {
+            import_decls: self.get_ir_import_decls(p.import_decls),
+            field_decls: self.get_ir_field_decls(p.field_decls, ir::VarScope::Global),
+            method_decls: p.method_decls.into_iter().map(|m| self.get_ir_method_decl(m)).collect(),
+        });
 
+        let errors = self.errors.take();
         if errors.is_empty() {
-            Ok(ir::IRRoot {
-                root: ir::ProgramDecl {
-                    import_decls: import_decls.unwrap(),
-                    field_decls: field_decls.unwrap(),
-                    method_decls: method_decls.unwrap(),
-                }
-            })
+            let mut ir = self.ir.take();
+            ir.root = root;
+            Ok(ir)
         } else {
             Err(errors)
         }
     }
 
-    fn get_ir_import_decls(&self, imports: Vec<ast::ImportDecl>) -> IRResult<Vec<ir::VarDecl>> {
-        let mut errors = Vec::new();
-        let mut imports_decls = Vec::new();
+    fn error(&self, e: SemanticCheckError) {
+        self.errors.borrow_mut().push(e);
+    }
 
-        for import_decl in imports {
-            let import = create_rc(ir::VarDecl0 {
-                type_: ir::Type::Int,
-                id: import_decl.id.clone(),
-                arr_len: None,
-            });
-            if let Err(e) = self.envs.borrow_mut().add_var(&import) {
-                errors.push(e);
-            } else {
-                imports_decls.push(import);
-            }
+    // runs `f` in a new scope, which is popped again however `f` went
+    fn with_scope<T>(&self, type_: EnvType, f: impl FnOnce() -> T) -> T {
+        self.envs.borrow_mut().push(type_);
+        let result = f();
+        self.envs.borrow_mut().pop();
+        result
+    }
+
+    // adds `var` to the symbol table and binds it in the current scope
+    fn declare_var(&self, var: ir::VarDecl) -> ir::VarId {
+        if var.arr_len.is_some_and(|len| len <= 0) {
+            self.error(SemanticCheckError::ArrayLenShouldPositive { name: var.name.clone(), span: var.span });
         }
 
-        if errors.is_empty() {
-            Ok(imports_decls)
-        } else {
-            Err(errors)
+        let (name, span) = (var.name.clone(), var.span);
+        let id = self.ir.borrow_mut().add_var(var);
+        if let Some(prev) = self.envs.borrow_mut().add_var(&name, id) {
+            self.error(SemanticCheckError::DuplicatedVar {
+                name,
+                span,
+                prev: self.ir.borrow()[prev].span,
+            });
         }
+        id
     }
 
-    fn get_ir_field_decls(&self, fields: Vec<ast::FieldDecl>) -> IRResult<Vec<ir::VarDecl>> {
-        let mut errors = Vec::new();
-        let mut field_decls: Vec<ir::VarDecl> = Vec::new();
-
-        for ids in fields {
-            for id in ids.field_ids {
-                let var = create_rc(ir::VarDecl0 {
-                    type_: ir::Type::from(&ids.type_),
-                    id: id.id,
-                    arr_len: id.arr_len,
-                });
-                if let Err(e) = self.envs.borrow_mut().add_var(&var) {
-                    errors.push(e);
-                } else {
-                    field_decls.push(var);
-                }
-            }
+    // looks up a variable, reporting and poisoning the name if it is undeclared
+    fn lookup_var(&self, id: &ast::Id) -> ir::VarId {
+        if let Some(var) = self.envs.borrow().get_var_decl(&id.node) {
+            return var;
         }
 
-        if errors.is_empty() {
-            Ok(field_decls)
-        } else {
-            Err(errors)
-        }
+        self.error(SemanticCheckError::UsedBeforeDeclared { name: id.node.clone(), span: id.span });
+        let scope = match self.envs.borrow().get_cur_scope_method_decl() {
+            Some(m) => ir::VarScope::Method(m),
+            None => ir::VarScope::Global,
+        };
+        let var = self.ir.borrow_mut().add_var(ir::VarDecl {
+            type_: ir::Type::Error,
+            name: id.node.clone(),
+            arr_len: None,
+            scope,
+            span: id.span,
+        });
+        self.envs.borrow_mut().poison_var(&id.node, var);
+        var
     }
 
-    fn get_ir_method_decls(&self, methods: Vec<ast::MethodDecl>) -> IRResult<Vec<ir::MethodDecl>> {
-        let mut errors = Vec::new();
-        let mut method_decls: Vec<ir::MethodDecl> = Vec::new();
-
-        for method in methods {
-            match self.get_ir_method_decl(method) {
-                Err(e) => errors.extend(e),
-                Ok(m) => method_decls.push(m),
-            }
-        }
-        
-        if errors.is_empty() {
-            Ok(method_decls)
-        } else {
-            Err(errors)
-        }
+    fn var(&self, id: ir::VarId) -> ir::VarDecl {
+        self.ir.borrow()[id].clone()
     }
 
-    fn get_ir_method_decl(&self, method: ast::MethodDecl) -> IRResult<ir::MethodDecl> {
-        let mut errors = Vec::new();
-        let args: Vec<ir::VarDecl> = method.args.iter().map(|arg| self.get_ir_method_arg(arg)).collect();
+    fn add_expr(&self, expr: ir::Expr) -> ir::ExprId {
+        self.ir.borrow_mut().add_expr(expr)
+    }
 
-        let method_decl = create_rc(ir::MethodDecl0 {
-            return_type: ir::ReturnType::from(&method.return_type),
-            name: method.id.clone(),
-            args: args.clone(),
-            block: None,
-        });
+    fn type_of(&self, expr: ir::ExprId) -> ir::Type {
+        self.ir.borrow()[expr].type_
+    }
 
-        if let Err(e) = self.envs.borrow_mut().add_method(&method_decl) {
-            errors.push(e);
-        }
+    fn span_of(&self, expr: ir::ExprId) -> Span {
+        self.ir.borrow()[expr].span
+    }
 
-        self.envs.borrow_mut().push(EnvType::Method(method_decl.clone()));
+    // whether `expr` has type `expected`, or has already been reported
+    fn has_type(&self, expr: ir::ExprId, expected: ir::Type) -> bool {
+        let actual = self.type_of(expr);
+        actual == expected || actual == ir::Type::Error
+    }
 
-        for arg in &method_decl.borrow().args {
-            if let Err(e) = self.envs.borrow_mut().add_var(arg) {
-                errors.push(e);
-            }
+    fn expect_type(&self, expr: ir::ExprId, expected: ir::Type) {
+        if !self.has_type(expr, expected) {
+            self.error(SemanticCheckError::OperandsTypeMismatch {
+                span: self.span_of(expr),
+                expected,
+                actual: self.type_of(expr),
+            });
         }
+    }
 
-        let block = self.get_ir_block(method.block);
-        if let Err(e) = block {
-            errors.extend(e);
-            return Err(errors);
+    fn expect_condition(&self, cond: ir::ExprId) {
+        if !self.has_type(cond, ir::Type::Bool) {
+            self.error(SemanticCheckError::ConditionTypeShouldBool {
+                span: self.span_of(cond),
+                actual: self.type_of(cond),
+            });
         }
+    }
 
-        self.envs.borrow_mut().pop();
+    // `+=`, `-=`, `++` and `--` need an `int` location; the value is only
+    // checked once the location is known to be fine
+    fn expect_int_update(&self, dst: &ir::Location, dst_span: Span, expr: Option<ir::ExprId>) {
+        let dst_type = self.var(dst.var).type_;
+        if dst_type != ir::Type::Int && dst_type != ir::Type::Error {
+            self.error(SemanticCheckError::OperandsTypeMismatch {
+                span: dst_span,
+                expected: ir::Type::Int,
+                actual: dst_type,
+            });
+        } else if let Some(expr) = expr {
+            self.expect_type(expr, ir::Type::Int);
+        }
+    }
 
-        Ok(create_rc(ir::MethodDecl0 {
-            return_type: ir::ReturnType::from(&method.return_type),
-            name: method.id.clone(),
-            args,
-            block: Some(block.unwrap()),
-        }))
+    fn get_ir_import_decls(&self, imports: Vec<ast::ImportDecl>) -> Vec<ir::VarId> {
+        imports
+            .into_iter()
+            .map(|import_decl| self.declare_var(ir::VarDecl {
+                type_: ir::Type::Int,
+                name: import_decl.id.node,
+                arr_len: None,
+                scope: ir::VarScope::Import,
+                span: import_decl.id.span,
+            }))
+            .collect()
     }
 
-    fn get_ir_method_arg(&self, arg: &ast::MethodArg) -> ir::VarDecl {
-        create_rc(ir::VarDecl0 {
-            type_: ir::Type::from(&arg.type_),
-            id: arg.id.clone(),
-            arr_len: None
-        })
+    fn get_ir_field_decls(&self, fields: Vec<ast::FieldDecl>, scope: ir::VarScope) -> Vec<ir::VarId> {
+        let mut field_decls = Vec::new();
+
+        for ids in fields {
+            for id in ids.field_ids {
+                // an array whose length is out of range is still an array
+                let arr_len = id.arr_len.as_ref().map(|len| self.get_ir_int_literal(len, false, id.span).unwrap_or(1));
+                let var = ir::VarDecl {
+                    type_: ir::Type::from(&ids.type_),
+                    name: id.id.node,
+                    arr_len,
+                    scope,
+                    span: id.span,
+                };
+                field_decls.push(self.declare_var(var));
+            }
+        }
+
+        field_decls
     }
 
-    fn get_ir_block(&self, block: ast::Block) -> IRResult<ir::Block> {
-        let mut errors = Vec::new();
+    fn get_ir_method_decl(&self, method: ast::MethodDecl) -> ir::MethodId {
+        // the method is added before its body is checked, so that it can
+        // call itself and its returns can refer to it
+        let method_id = self.ir.borrow_mut().add_method(ir::MethodDecl {
+            return_type: ir::ReturnType::from(&method.return_type),
+            name: method.id.node.clone(),
+            args: Vec::new(),
+            block: ir::Block::default(),
+            falls_off: false,
+            span: method.id.span,
+        });
 
-        let field_decls = match self.get_ir_field_decls(block.field_decls) {
-            Err(e) => {
-                errors.extend(e);
-                None
-            },
-            Ok(f) => Some(f),
-        };
+        // methods share the global scope with imports and fields
+        let prev = self.envs.borrow().get_global_var_decl(&method.id.node).map(|var| self.var(var).span);
+        let prev = prev.or_else(|| {
+            let prev = self.envs.borrow_mut().add_method(&method.id.node, method_id);
+            prev.map(|m| self.ir.borrow()[m].span)
+        });
+        if let Some(prev) = prev {
+            self.error(SemanticCheckError::DuplicatedMethod {
+                name: method.id.node.clone(),
+                span: method.id.span,
+                prev,
+            });
+        }
 
-        let mut statements = Vec::new();
-        for statement in block.statements {
-            match self.get_ir_statement(statement) {
-                Err(e) => errors.extend(e),
-                Ok(s) => statements.push(s),
+        let block = self.with_scope(EnvType::Method(method_id), || {
+            for arg in &method.args {
+                let arg = self.declare_var(self.get_ir_method_arg(arg, method_id));
+                self.ir.borrow_mut()[method_id].args.push(arg);
             }
-        }
+            self.get_ir_block(method.block, ir::VarScope::Method(method_id))
+        });
 
-        if errors.is_empty() {
-            Ok(ir::Block {
-                field_decls: field_decls.unwrap(),
-                statements,
-            })
-        } else {
-            Err(errors)
+        self.ir.borrow_mut()[method_id].block = block;
+        method_id
+    }
+
+    fn get_ir_method_arg(&self, arg: &ast::MethodArg, method: ir::MethodId) -> ir::VarDecl {
+        ir::VarDecl {
+            type_: ir::Type::from(&arg.type_),
+            name: arg.id.node.clone(),
+            arr_len: None,
+            scope: ir::VarScope::Method(method),
+            span: arg.id.span,
         }
     }
 
-    fn get_ir_statement(&self, statement: ast::Statement) -> IRResult<ir::Statement> {
-        match statement {
-            ast::Statement::Assign(assign) => match self.get_ir_assign(assign) {
-                Ok(a) => Ok(create_rc(ir::Statement0::Assign(a))),
-                Err(e) => Err(e),
-            },
-            ast::Statement::Break => match self.get_ir_break() {
-                Ok(b) => Ok(create_rc(ir::Statement0::Break(b))),
-                Err(e) => Err(e),
-            },
-            ast::Statement::Continue => match self.get_ir_continue() {
-                Ok(c) => Ok(create_rc(ir::Statement0::Continue(c))),
-                Err(e) => Err(e),
-            },
-            ast::Statement::IfElse(if_else) => match self.get_ir_if_else(if_else) {
-                Ok(ie) => Ok(create_rc(ir::Statement0::IfElse(ie))),
-                Err(e) => Err(e),
-            },
-            ast::Statement::Loop(fo) => match self.get_ir_for(fo) {
-                Ok(f) => Ok(create_rc(ir::Statement0::For(f))),
-                Err(e) => Err(e),
-            },
-            ast::Statement::MethodCall(method) => match self.get_ir_method_call(method) {
-                Ok(m) => Ok(create_rc(ir::Statement0::MethodCall(m))),
-                Err(e) => Err(e),
-            },
-            ast::Statement::Return(ret) => match self.get_ir_return(ret) {
-                Ok(r) => Ok(create_rc(ir::Statement0::Return(r))),
-                Err(e) => Err(e),
-            },
-            ast::Statement::While(whl) => match self.get_ir_while(whl) {
-                Ok(w) => Ok(create_rc(ir::Statement0::While(w))),
-                Err(e) => Err(e),
-            },
+    fn get_ir_block(&self, block: ast::Block, scope: ir::VarScope) -> ir::Block {
+        let field_decls = self.get_ir_field_decls(block.field_decls, scope);
+
+        let statements = block.statements
+            .into_iter()
+            // statements that failed to parse have already been reported
+            .filter(|s| !matches!(s.node, ast::Statement_::Error))
+            .map(|s| self.get_ir_statement(s, scope))
+            .collect();
+
+        ir::Block {
+            field_decls,
+            statements,
         }
     }
 
-    fn get_ir_assign(&self, assign: ast::Assign) -> IRResult<ir::Assign> {
-        let dst = match self.get_ir_location(assign.dst) {
-            Ok(d) => d,
-            Err(e) => {
-                return Err(e);
-            }
+    fn get_ir_statement(&self, statement: ast::Statement, scope: ir::VarScope) -> ir::StmtId {
+        let span = statement.span;
+        let stmt = match statement.node {
+            ast::Statement_::Assign(assign) => ir::StmtType::Assign(self.get_ir_assign(assign)),
+            ast::Statement_::Break => ir::StmtType::Break(self.get_ir_break(span)),
+            ast::Statement_::Continue => ir::StmtType::Continue(self.get_ir_continue(span)),
+            ast::Statement_::IfElse(if_else) => ir::StmtType::IfElse(self.get_ir_if_else(if_else, scope)),
+            ast::Statement_::Loop(fo) => ir::StmtType::For(self.get_ir_for(fo, span, scope)),
+            ast::Statement_::MethodCall(method) => ir::StmtType::MethodCall(self.get_ir_method_call(method).0),
+            ast::Statement_::Return(ret) => ir::StmtType::Return(self.get_ir_return(ret, span)),
+            ast::Statement_::While(whl) => ir::StmtType::While(self.get_ir_while(whl, span, scope)),
+            ast::Statement_::Error => unreachable!("syntax errors are filtered out by get_ir_block"),
         };
+        self.ir.borrow_mut().add_stmt(ir::Statement { stmt, span })
+    }
 
-        let assign_expr = match self.get_ir_assign_expr(assign.assign_expr) {
-            Ok(a) => a,
-            Err(e) => {
-                return Err(e);
-            }
-        };
+    fn get_ir_assign(&self, assign: ast::Assign) -> ir::Assign {
+        let dst_span = assign.dst.span();
+        let dst = self.get_ir_location(assign.dst);
+        let assign_expr = self.get_ir_assign_expr(assign.assign_expr);
 
-        match assign_expr.clone() {
-            ir::AssignExpr::AssignOpExpr(a) => {
-                match a.assign_op {
-                    ir::AssignOp::CompoundAssignOp(_) => {
-                        if dst.id.borrow().type_ != ir::Type::Int {
-                            return Err(vec![SemanticCheckError::OperandsTypeMismatch]);
-                        }
-                    },
-                    ir::AssignOp::Assign => {
-                        if dst.id.borrow().type_ != a.expr.borrow().type_ {
-                            return Err(vec![SemanticCheckError::OperandsTypeMismatch]);
-                        }
-                    },
-                }
-            },
-            ir::AssignExpr::Increment(_) => {
-                if dst.id.borrow().type_ != ir::Type::Int {
-                    return Err(vec![SemanticCheckError::OperandsTypeMismatch]);
+        match &assign_expr {
+            ir::AssignExpr::AssignOpExpr(ir::AssignOpExpr { assign_op: ir::AssignOp::Assign, expr }) => {
+                let dst_type = self.var(dst.var).type_;
+                if dst_type != ir::Type::Error {
+                    self.expect_type(*expr, dst_type);
                 }
-            },
+            }
+            ir::AssignExpr::AssignOpExpr(a) => self.expect_int_update(&dst, dst_span, Some(a.expr)),
+            ir::AssignExpr::Increment(_) => self.expect_int_update(&dst, dst_span, None),
         }
 
-        Ok(ir::Assign {
+        ir::Assign {
             dst,
             assign_expr,
-        })
+        }
     }
 
-    fn get_ir_location(&self, dst: ast::Location) -> IRResult<ir::Location> {
-        let mut errors = Vec::new();
+    fn get_ir_location(&self, dst: ast::Location) -> ir::Location {
+        let id = dst.id().clone();
+        let span = dst.span();
 
-        let id = match dst.clone() {
-            ast::Location::Id(id) => id,
-            ast::Location::IdExpr(arr) => arr.id,
-        };
-
-        let var = self.envs.borrow().get_var_decl(&id);
-        if var.is_none() {
-            errors.push(SemanticCheckError::UsedBeforeDeclared(id.clone()));
-            return Err(errors);
+        let var = self.lookup_var(&id);
+        let decl = self.var(var);
+        if decl.scope == ir::VarScope::Import {
+            self.error(SemanticCheckError::NotAVariable { name: id.node.clone(), span: id.span });
         }
-        let var = var.unwrap();
+        let checked = decl.type_ != ir::Type::Error && decl.scope != ir::VarScope::Import;
 
-        let array_len = match dst {
+        let index = match dst {
             ast::Location::Id(_) => {
-                if var.borrow().arr_len.is_some() {
-                    errors.push(SemanticCheckError::LocationTypeMismatch);
+                if checked && decl.arr_len.is_some() {
+                    self.error(SemanticCheckError::LocationTypeMismatch { name: id.node, span, is_array: true });
                 }
                 None
             }
             ast::Location::IdExpr(arr) => {
-                match self.get_ir_expr(arr.array_len.clone()) {
-                    Ok(i) => {
-                        let type_ = i.borrow().type_;
-                        let arr_len = &var.borrow().arr_len;
-                        if type_ == ir::Type::Int {
-                            if arr_len.is_some() {
-                                Some(i)
-                            } else {
-                                errors.push(SemanticCheckError::LocationTypeMismatch);
-                                None
-                            }
-                        } else {
-                            errors.push(SemanticCheckError::TypeOfExprMustInt(id.clone()));
-                            None
-                        }
-                    }
-                    Err(e) => {
-                        errors.extend(e);
-                        None
-                    }
+                let index = self.get_ir_expr(*arr.array_len);
+                if checked && decl.arr_len.is_none() {
+                    self.error(SemanticCheckError::LocationTypeMismatch { name: id.node, span, is_array: false });
+                } else if !self.has_type(index, ir::Type::Int) {
+                    self.error(SemanticCheckError::TypeOfExprMustInt {
+                        name: id.node,
+                        span: self.span_of(index),
+                        actual: self.type_of(index),
+                    });
                 }
+                Some(index)
             }
         };
 
-        if errors.is_empty() {
-            Ok(ir::Location {
-                id: var,
-                array_len,
-            })
-        } else {
-            Err(errors)
+        ir::Location {
+            var,
+            index,
         }
     }
 
-    fn get_ir_expr(&self, expr: ast::Expr) -> IRResult<ir::Expr> {    
-        let expr = match *expr {
-            ast::Expr_::Location(l) => match self.get_ir_location(l) {
-                Ok(l) => ir::ExprType::Location(l),
-                Err(e) => return Err(e),
-            },
-            ast::Expr_::MethodCall(m) => match self.get_ir_method_call(m) {
-                Ok(m) => ir::ExprType::MethodCall(m),
-                Err(e) => return Err(e),
-            },
-            ast::Expr_::Literal(l) => ir::ExprType::Literal(ir::Literal::from(&l)),
-            ast::Expr_::LenId(l) => match self.get_ir_len_id(l) {
-                Ok(l) => ir::ExprType::LenId(l),
-                Err(e) => return Err(e),
-            },
-            ast::Expr_::Binary(b) => match self.get_ir_binary(b) {
-                Ok(b) => ir::ExprType::Binary(b),
-                Err(e) => return Err(e),
-            },
-            ast::Expr_::Unary(u) => match self.get_ir_unary(u) {
-                Ok(u) => ir::ExprType::Unary(u),
-                Err(e) => return Err(e),
+    fn get_ir_expr(&self, expr: Spanned<ast::Expr_>) -> ir::ExprId {
+        let span = expr.span;
+        let (expr, type_) = match expr.node {
+            ast::Expr_::Location(l) => {
+                let l = self.get_ir_location(l);
+                let decl = self.var(l.var);
+                // a whole array has already been reported by get_ir_location
+                let type_ = if decl.arr_len.is_some() && l.index.is_none() { ir::Type::Error } else { decl.type_ };
+                (ir::ExprType::Location(l), type_)
+            }
+            ast::Expr_::MethodCall(m) => {
+                let (call, type_) = self.get_ir_method_call(m);
+                let type_ = match (&call, type_) {
+                    (ir::MethodCall::Method(m), ir::Type::Void) => {
+                        let name = self.ir.borrow()[m.method].name.clone();
+                        self.error(SemanticCheckError::MethodMustReturnValue { name, span });
+                        ir::Type::Error
+                    }
+                    (_, type_) => type_,
+                };
+                (ir::ExprType::MethodCall(call), type_)
+            }
+            ast::Expr_::Literal(l) => {
+                let l = self.get_ir_literal(&l, span);
+                let type_ = match l {
+                    // characters are just their ASCII value
+                    ir::Literal::IntLiteral(_) | ir::Literal::CharLiteral(_) => ir::Type::Int,
+                    ir::Literal::BoolLiteral(_) => ir::Type::Bool,
+                };
+                (ir::ExprType::Literal(l), type_)
+            }
+            ast::Expr_::LenId(l) => (ir::ExprType::LenId(self.get_ir_len_id(l)), ir::Type::Int),
+            ast::Expr_::Binary(b) => {
+                let b = self.get_ir_binary(b);
+                let type_ = b.op.get_return_type();
+                (ir::ExprType::Binary(b), type_)
+            }
+            // `2147483648` is only in range once negated, so negative literals
+            // are folded into a single literal before range checking
+            ast::Expr_::Unary(u) => match negated_int_literal(&u) {
+                Some(i) => {
+                    let i = self.get_ir_int_literal(i, true, span).unwrap_or(0);
+                    (ir::ExprType::Literal(ir::Literal::IntLiteral(i)), ir::Type::Int)
+                }
+                None => {
+                    let u = self.get_ir_unary(u);
+                    let type_ = match u.op {
+                        ir::UnaryOp::NegInt => ir::Type::Int,
+                        ir::UnaryOp::NegBool => ir::Type::Bool,
+                    };
+                    (ir::ExprType::Unary(u), type_)
+                }
             }
         };
 
-        let type_ = self.get_ir_expr_type(&expr);
-
-        Ok(create_rc(ir::Expr0 {
+        self.add_expr(ir::Expr {
             type_,
             expr,
-        }))
+            span,
+        })
     }
 
-    fn get_ir_expr_type(&self, e: &ir::ExprType) -> ir::Type {
-        match e {
-            ir::ExprType::Location(l) => l.id.borrow().type_,
-            ir::ExprType::MethodCall(m) => match m {
-                ir::MethodCall::Method(m) => match m.decl.borrow().return_type {
-                    ir::ReturnType::Void => ir::Type::Void,
-                    ir::ReturnType::Type(t) => t,
-                }
-                ir::MethodCall::Callout(_) => ir::Type::Int,
+    fn get_ir_literal(&self, l: &ast::Literal, span: Span) -> ir::Literal {
+        match l {
+            ast::Literal::IntLiteral(i) => ir::Literal::IntLiteral(self.get_ir_int_literal(i, false, span).unwrap_or(0)),
+            ast::Literal::BoolLiteral(b) => match b {
+                ast::BoolLiteral::True => ir::Literal::BoolLiteral(true),
+                ast::BoolLiteral::False => ir::Literal::BoolLiteral(false),
             },
-            ir::ExprType::Literal(l) => match l {
-                ir::Literal::IntLiteral(_) => ir::Type::Int,
-                ir::Literal::BoolLiteral(_) => ir::Type::Bool,
-                ir::Literal::CharLiteral(_) => ir::Type::Char,
-            }
-            ir::ExprType::LenId(_) => ir::Type::Int,
-            ir::ExprType::Binary(b) => b.op.get_return_type(),
-            ir::ExprType::Unary(u) => u.expr.borrow().type_,
+            ast::Literal::CharLiteral(c) => ir::Literal::CharLiteral(c.to_owned()),
         }
     }
 
-    fn get_ir_len_id(&self, id: String) -> IRResult<String> {
-        let mut errors = Vec::new();
-        
-        let var = self.envs.borrow().get_var_decl(&id);
-        if var.is_none() {
-            errors.push(SemanticCheckError::UsedBeforeDeclared(id));
-            return Err(errors);
+    // reports literals that don't fit in an `int`
+    fn get_ir_int_literal(&self, i: &ast::IntLiteral, negated: bool, span: Span) -> Option<i32> {
+        let value = i.value().map(|v| if negated { -v } else { v });
+        let value = value.and_then(|v| i32::try_from(v).ok());
+        if value.is_none() {
+            self.error(SemanticCheckError::IntLiteralOutOfRange {
+                literal: if negated { format!("-{}", i.text()) } else { i.text().to_string() },
+                span,
+            });
         }
-        let var = var.unwrap();
+        value
+    }
 
-        let arr_len = &var.borrow().arr_len;
-        if arr_len.is_some() {
-            Ok(id)
-        } else {
-            errors.push(SemanticCheckError::IdMustArray(var.borrow().id.clone()));
-            Err(errors)
+    fn get_ir_len_id(&self, id: ast::Id) -> ir::VarId {
+        let var = self.lookup_var(&id);
+        let decl = self.var(var);
+        if decl.type_ != ir::Type::Error && decl.arr_len.is_none() {
+            self.error(SemanticCheckError::IdMustArray { name: id.node, span: id.span });
         }
+        var
     }
 
-    fn get_ir_binary(&self, binary: ast::Binary) -> IRResult<ir::Binary> {
-        let mut errors = Vec::new();
-        let lhs = match self.get_ir_expr(binary.lhs) {
-            Ok(l) => Some(l),
-            Err(e) => {
-                errors.extend(e);
-                None
-            }
-        };
-        let rhs = match self.get_ir_expr(binary.rhs) {
-            Ok(r) => Some(r),
-            Err(e) => {
-                errors.extend(e);
-                None
-            }
-        };
+    fn get_ir_binary(&self, binary: ast::Binary) -> ir::Binary {
+        let lhs = self.get_ir_expr(*binary.lhs);
+        let rhs = self.get_ir_expr(*binary.rhs);
+        let op = ir::BinaryOp::from(&binary.op);
 
-        if !errors.is_empty() {
-            return Err(errors);
+        let (lhs_type, rhs_type) = (self.type_of(lhs), self.type_of(rhs));
+        if lhs_type == ir::Type::Error || rhs_type == ir::Type::Error {
+            return ir::Binary { lhs, rhs, op };
         }
 
-        let lhs = lhs.unwrap();
-        let rhs = rhs.unwrap();
-
-        if lhs.borrow().type_ != rhs.borrow().type_ {
-            errors.push(SemanticCheckError::OperandsTypeMismatch);
-            return Err(errors);
+        if lhs_type != rhs_type {
+            self.error(SemanticCheckError::OperandsTypeMismatch {
+                span: self.span_of(rhs),
+                expected: lhs_type,
+                actual: rhs_type,
+            });
+            return ir::Binary { lhs, rhs, op };
         }
 
-        let op = ir::BinaryOp::from(&binary.op);
-        let operand_type = lhs.borrow().type_;
+        let operand_type = lhs_type;
         match op {
             ir::BinaryOp::Add
             | ir::BinaryOp::Sub
@@ -485,324 +490,298 @@ impl SemanticAnalyzer {
             | ir::BinaryOp::LT
             | ir::BinaryOp::LE
                 if operand_type == ir::Type::Int => (),
-            ir::BinaryOp::EQ | ir::BinaryOp::NE => (),
+            ir::BinaryOp::EQ | ir::BinaryOp::NE if operand_type != ir::Type::Void => (),
             ir::BinaryOp::Or | ir::BinaryOp::And if operand_type == ir::Type::Bool => (),
+            ir::BinaryOp::Or | ir::BinaryOp::And | ir::BinaryOp::EQ | ir::BinaryOp::NE => {
+                self.error(SemanticCheckError::OperandsTypeMismatch {
+                    span: self.span_of(lhs).to(self.span_of(rhs)),
+                    expected: ir::Type::Bool,
+                    actual: operand_type,
+                });
+            }
             _ => {
-                errors.push(SemanticCheckError::OperandsTypeMismatch);
-                return Err(errors);
+                self.error(SemanticCheckError::OperandsTypeMismatch {
+                    span: self.span_of(lhs).to(self.span_of(rhs)),
+                    expected: ir::Type::Int,
+                    actual: operand_type,
+                });
             }
         }
-        Ok(ir::Binary{ lhs, rhs, op })
+        ir::Binary{ lhs, rhs, op }
     }
 
-    fn get_ir_unary(&self, unary: ast::Unary) -> IRResult<ir::Unary> {
-        let expr = match self.get_ir_expr(unary.expr) {
-            Ok(u) => u,
-            Err(e) => return Err(e),
+    fn get_ir_unary(&self, unary: ast::Unary) -> ir::Unary {
+        let expr = self.get_ir_expr(*unary.expr);
+        let (op, expected) = match unary.op {
+            ast::UnaryOp::NegInt => (ir::UnaryOp::NegInt, ir::Type::Int),
+            ast::UnaryOp::NegBool => (ir::UnaryOp::NegBool, ir::Type::Bool),
         };
-        let type_ = expr.borrow().type_;
-        match unary.op {
-            ast::UnaryOp::NegInt if type_ == ir::Type::Int => Ok(ir::Unary {
-                expr,
-                op: ir::UnaryOp::NegInt,
-            }),
-            ast::UnaryOp::NegBool if type_ == ir::Type::Bool => Ok(ir::Unary {
-                expr,
-                op: ir::UnaryOp::NegBool,
-            }),
-            _ => Err(vec![SemanticCheckError::OperandsTypeMismatch])
-        }
+        self.expect_type(expr, expected);
+        ir::Unary { expr, op }
     }
 
-    fn get_ir_assign_expr(&self, expr: ast::AssignExpr) -> IRResult<ir::AssignExpr> {
+    fn get_ir_assign_expr(&self, expr: ast::AssignExpr) -> ir::AssignExpr {
         match expr {
-            ast::AssignExpr::AssignOpExpr(a) => {
-                let op = ir::AssignOp::from(&a.assign_op);
-                let expr = match self.get_ir_expr(a.expr) {
-                    Ok(expr) => expr,
-                    Err(e) => return Err(e),
-                };
-                Ok(ir::AssignExpr::AssignOpExpr(ir::AssignOpExpr {
-                    assign_op: op,
-                    expr,
-                }))
-            }
-            ast::AssignExpr::Increment(inc) => Ok(ir::AssignExpr::Increment(ir::Increment::from(&inc))),
+            ast::AssignExpr::AssignOpExpr(a) => ir::AssignExpr::AssignOpExpr(ir::AssignOpExpr {
+                assign_op: ir::AssignOp::from(&a.assign_op),
+                expr: self.get_ir_expr(*a.expr),
+            }),
+            ast::AssignExpr::Increment(inc) => ir::AssignExpr::Increment(ir::Increment::from(&inc)),
         }
     }
 
-    fn get_ir_break(&self) -> IRResult<ir::Break> {
-        let for_ = self.envs.borrow().get_cur_scope_for();
-        let while_ = self.envs.borrow().get_cur_scope_while();
-
-        if for_.is_none() && while_.is_none() {
-            Err(vec![SemanticCheckError::InvalidBreak])
-        } else if !for_.is_none() {
-            Ok(ir::Break::For(for_.unwrap()))
-        } else {
-            Ok(ir::Break::While(while_.unwrap()))
-        }
+    // a misplaced `break` or `continue` gets a dangling target; the IR is
+    // never handed out when there were errors
+    fn get_ir_break(&self, span: Span) -> ir::LoopId {
+        let target = self.envs.borrow().get_cur_scope_loop();
+        target.unwrap_or_else(|| {
+            self.error(SemanticCheckError::InvalidBreak { span });
+            ir::LoopId(u32::MAX)
+        })
     }
 
-    fn get_ir_continue(&self) -> IRResult<ir::Continue> {
-        let for_ = self.envs.borrow().get_cur_scope_for();
-        let while_ = self.envs.borrow().get_cur_scope_while();
-
-        if for_.is_none() && while_.is_none() {
-            Err(vec![SemanticCheckError::InvalidContinue])
-        } else if !for_.is_none() {
-            Ok(ir::Continue::For(for_.unwrap()))
-        } else {
-            Ok(ir::Continue::While(while_.unwrap()))
-        }
+    fn get_ir_continue(&self, span: Span) -> ir::LoopId {
+        let target = self.envs.borrow().get_cur_scope_loop();
+        target.unwrap_or_else(|| {
+            self.error(SemanticCheckError::InvalidContinue { span });
+            ir::LoopId(u32::MAX)
+        })
     }
 
-    fn get_ir_if_else(&self, if_else: ast::IfElse) -> IRResult<ir::IfElse> {
-        let cond = match self.get_ir_expr(if_else.cond) {
-            Ok(c) => c,
-            Err(e) => return Err(e),
-        };
+    fn get_ir_if_else(&self, if_else: ast::IfElse, scope: ir::VarScope) -> ir::IfElse {
+        let cond = self.get_ir_expr(*if_else.cond);
+        self.expect_condition(cond);
 
-        if cond.borrow().type_ != ir::Type::Bool {
-            return Err(vec![SemanticCheckError::ConditionTypeShouldBool]);
-        }
-
-        let tmp = create_rc(ir::IfElse0 {
-            cond: cond.clone(),
-            if_block: None,
-            else_block: None,
-        });
-
-        self.envs.borrow_mut().push(EnvType::If(tmp.clone()));
-
-        let if_block = match self.get_ir_block(if_else.if_block) {
-            Ok(b) => b,
-            Err(e) => return Err(e),
-        };
-
-        self.envs.borrow_mut().pop();
-        self.envs.borrow_mut().push(EnvType::Else(tmp.clone()));
+        let if_block = self.with_scope(EnvType::If, || self.get_ir_block(if_else.if_block, scope));
+        let else_block = if_else.else_block.map(|b| self.with_scope(EnvType::Else, || self.get_ir_block(b, scope)));
 
-        let else_block = match if_else.else_block {
-            Some(b) => {
-                match self.get_ir_block(b) {
-                    Ok(b) => Some(b),
-                    Err(e) => return Err(e),
-                }
-            }
-            None => None,
-        };
-
-        self.envs.borrow_mut().pop();
-
-        Ok(create_rc(ir::IfElse0 {
+        ir::IfElse {
             cond,
-            if_block: Some(if_block),
+            if_block,
             else_block,
-        }))
-    }
-
-    fn get_ir_for(&self, lop: ast::Loop) -> IRResult<ir::For> {
-        let id = match self.envs.borrow().get_var_decl(&lop.id) {
-            Some(id) => id,
-            None => return Err(vec![SemanticCheckError::UsedBeforeDeclared(lop.id.clone())]),
-        };
-
-        let init_expr = match self.get_ir_expr(lop.init_expr) {
-            Ok(i) => i,
-            Err(e) => return Err(e),
-        };
-        if init_expr.borrow().type_ != ir::Type::Int {
-            return Err(vec![SemanticCheckError::OperandsTypeMismatch]);
         }
+    }
 
-        let incre_expr = match self.get_ir_expr(lop.incre_expr) {
-            Ok(i) => i,
-            Err(e) => return Err(e),
-        };
-        if incre_expr.borrow().type_ != ir::Type::Bool {
-            return Err(vec![SemanticCheckError::OperandsTypeMismatch]);
+    fn get_ir_for(&self, lop: ast::Loop, span: Span, scope: ir::VarScope) -> ir::For {
+        let var = self.lookup_var(&lop.id);
+        let decl = self.var(var);
+        if decl.scope == ir::VarScope::Import {
+            self.error(SemanticCheckError::NotAVariable { name: decl.name, span: lop.id.span });
+        } else if decl.type_ != ir::Type::Error && (decl.type_ != ir::Type::Int || decl.arr_len.is_some()) {
+            self.error(SemanticCheckError::InvalidLoopVar { name: decl.name, span: lop.id.span });
         }
 
-        let update = match self.get_ir_for_update(lop.update) {
-            Ok(u) => u,
-            Err(e) => return Err(e),
-        };
+        let init_expr = self.get_ir_expr(*lop.init_expr);
+        self.expect_type(init_expr, ir::Type::Int);
 
-        let for_ = create_rc(ir::For0 {
-            id: id.clone(),
-            init_expr: init_expr.clone(),
-            incre_expr: incre_expr.clone(),
-            update: update.clone(),
-            block: None,
-        });
+        let cond = self.get_ir_expr(*lop.incre_expr);
+        self.expect_condition(cond);
 
-        self.envs.borrow_mut().push(EnvType::For(for_.clone()));
+        let update = self.get_ir_for_update(lop.update);
 
-        let block = match self.get_ir_block(lop.block) {
-            Ok(b) => b,
-            Err(e) => return Err(e),
-        };
+        let loop_ = self.ir.borrow_mut().add_loop(ir::Loop { kind: ir::LoopKind::For, span });
+        let block = self.with_scope(EnvType::For(loop_), || self.get_ir_block(lop.block, scope));
 
-        self.envs.borrow_mut().pop();
-
-        Ok(create_rc(ir::For0 {
-            id,
+        ir::For {
+            loop_,
+            var,
             init_expr,
-            incre_expr,
+            cond,
             update,
-            block: Some(block),
-        }))
+            block,
+        }
     }
 
-    fn get_ir_for_update(&self, update: ast::ForUpdate) -> IRResult<ir::ForUpdate> {
-        let id = match self.get_ir_location(update.location) {
-            Ok(l) => l,
-            Err(e) => return Err(e),
-        };
+    fn get_ir_for_update(&self, update: ast::ForUpdate) -> ir::ForUpdate {
+        let dst_span = update.location.span();
+        let dst = self.get_ir_location(update.location);
 
         let update_expr = match update.update_expr {
             ast::ForUpdateExpr::AssignExpr(a) => {
                 let compound_assign_op = ir::CompoundAssignOp::from(&a.compound_assign_op);
-                let expr = match self.get_ir_expr(a.expr) {
-                    Ok(expr) => {
-                        if expr.borrow().type_ != ir::Type::Int {
-                            return Err(vec![SemanticCheckError::OperandsTypeMismatch]);
-                        }
-                        expr
-                    }
-                    Err(e) => return Err(e),
-                };
+                let expr = self.get_ir_expr(*a.expr);
+                self.expect_int_update(&dst, dst_span, Some(expr));
                 ir::ForUpdateExpr::AssignExpr(ir::ForUpdateAssignExpr { compound_assign_op, expr })
             },
             ast::ForUpdateExpr::Increment(i) => {
+                self.expect_int_update(&dst, dst_span, None);
                 ir::ForUpdateExpr::Increment(ir::Increment::from(&i))
             },
         };
 
-        Ok(ir::ForUpdate {
-            id,
+        ir::ForUpdate {
+            dst,
             update_expr
-        })
+        }
     }
 
-    fn get_ir_method_call(&self, method: ast::MethodCall) -> IRResult<ir::MethodCall> {
+    // returns the call along with the type of its result
+    fn get_ir_method_call(&self, method: ast::MethodCall) -> (ir::MethodCall, ir::Type) {
         match method {
-            ast::MethodCall::Method(m) => match self.get_ir_method(m) {
-                Ok(m) => Ok(ir::MethodCall::Method(m)),
-                Err(e) => Err(e),
-            },
-            ast::MethodCall::Callout(c) => match self.get_ir_callout(c) {
-                Ok(c) => Ok(ir::MethodCall::Callout(c)),
-                Err(e) => Err(e),
-            }
+            ast::MethodCall::Method(m) => self.get_ir_method(m),
+            ast::MethodCall::Callout(c) => (ir::MethodCall::Callout(self.get_ir_callout(c)), ir::Type::Int),
         }
     }
 
-    fn get_ir_method(&self, method: ast::MethodCall0) -> IRResult<ir::MethodCall0> {
-        let decl = match self.envs.borrow().get_method_decl(&method.name.id) {
+    fn get_ir_method(&self, method: ast::MethodCall0) -> (ir::MethodCall, ir::Type) {
+        let name = &method.name.id;
+
+        // locals may shadow methods and imports, in which case they can't be
+        // called; imports and names that could not be resolved become
+        // external calls
+        let var = self.envs.borrow().get_var_decl(&name.node).map(|v| self.var(v));
+        let method_id = match &var {
+            Some(var) if var.scope == ir::VarScope::Import || var.type_ == ir::Type::Error => None,
+            Some(_) => {
+                self.error(SemanticCheckError::NotAMethod { name: name.node.clone(), span: name.span });
+                None
+            }
+            None => {
+                let method_id = self.envs.borrow().get_method_decl(&name.node);
+                if method_id.is_none() {
+                    self.error(SemanticCheckError::UsedBeforeDeclared { name: name.node.clone(), span: name.span });
+                }
+                method_id
+            }
+        };
+        let method_id = match method_id {
             Some(m) => m,
-            None => return Err(vec![SemanticCheckError::UsedBeforeDeclared(method.name.id.clone())]),
+            None => {
+                let args = method.args.into_iter().map(|arg| ir::ImportArg::Expr(self.get_ir_expr(*arg))).collect();
+                let type_ = match var {
+                    Some(var) if var.scope == ir::VarScope::Import => ir::Type::Int,
+                    _ => ir::Type::Error,
+                };
+                return (ir::MethodCall::Callout(ir::MethodCall1 { name: name.node.clone(), args }), type_);
+            }
         };
 
         let mut args = Vec::new();
         for arg in method.args {
-            match self.get_ir_expr(arg) {
-                Ok(a) => args.push(a),
-                Err(e) => return Err(e),
-            }
-        }
-
-        if args.len() != decl.borrow().args.len() {
-            return Err(vec![SemanticCheckError::MethodSignatureMismatch]);
+            args.push(self.get_ir_method_arg_expr(*arg));
         }
 
-        for (arg, arg_decl) in args.iter().zip(decl.borrow().args.iter()) {
-            if arg.borrow().type_ != arg_decl.borrow().type_ {
-                return Err(vec![SemanticCheckError::InvalidMethodArgs]);
+        let (params, return_type) = {
+            let decl = &self.ir.borrow()[method_id];
+            (decl.args.clone(), ir::ReturnType::to_type(&decl.return_type))
+        };
+        if args.len() != params.len() {
+            self.error(SemanticCheckError::MethodSignatureMismatch {
+                name: name.node.clone(),
+                span: method.span,
+                expected: params.len(),
+                actual: args.len(),
+            });
+        } else {
+            for (&arg, &param) in args.iter().zip(params.iter()) {
+                let expected = self.var(param).type_;
+                if !self.has_type(arg, expected) {
+                    self.error(SemanticCheckError::InvalidMethodArgs {
+                        name: name.node.clone(),
+                        span: self.span_of(arg),
+                        expected,
+                        actual: self.type_of(arg),
+                    });
+                }
             }
         }
 
-        Ok(ir::MethodCall0 {
-            decl,
+        let call = ir::MethodCall::Method(ir::MethodCall0 {
+            method: method_id,
             args,
-        })
+        });
+        (call, return_type)
     }
 
-    fn get_ir_callout(&self, callout: ast::MethodCall1) -> IRResult<ir::MethodCall1> {
-        let mut args = Vec::new();
-        for arg in callout.args {
-            match arg {
-                ast::ImportArg::Expr(e) => {
-                    match self.get_ir_expr(e) {
-                        Ok(a) => args.push(ir::ImportArg::Expr(a)),
-                        Err(e) => return Err(e),
-                    }
-                },
-                ast::ImportArg::StringLiteral(s) => args.push(ir::ImportArg::StringLiteral(s)),
+    // like `get_ir_expr`, but whole arrays get their own error
+    fn get_ir_method_arg_expr(&self, arg: Spanned<ast::Expr_>) -> ir::ExprId {
+        if let ast::Expr_::Location(ast::Location::Id(id)) = &arg.node {
+            let var = self.envs.borrow().get_var_decl(&id.node);
+            if let Some(var) = var.filter(|v| self.var(*v).arr_len.is_some()) {
+                self.error(SemanticCheckError::ArrayAsMethodArg { name: id.node.clone(), span: id.span });
+                return self.add_expr(ir::Expr {
+                    type_: ir::Type::Error,
+                    expr: ir::ExprType::Location(ir::Location { var, index: None }),
+                    span: arg.span,
+                });
             }
         }
+        self.get_ir_expr(arg)
+    }
+
+    fn get_ir_callout(&self, callout: ast::MethodCall1) -> ir::MethodCall1 {
+        let args = callout.args
+            .into_iter()
+            .map(|arg| match arg {
+                ast::ImportArg::Expr(e) => ir::ImportArg::Expr(self.get_ir_expr(*e)),
+                ast::ImportArg::StringLiteral(s) => ir::ImportArg::StringLiteral(s.node),
+            })
+            .collect();
 
-        Ok(ir::MethodCall1 {
-            name: callout.name.id.clone(),
+        ir::MethodCall1 {
+            name: callout.name.id.node.clone(),
             args,
-        })
+        }
     }
 
-    fn get_ir_return(&self, ret: ast::Return) -> IRResult<ir::Return> {
-        let val = match ret.expr {
-            Some(expr) => match self.get_ir_expr(expr) {
-                Ok(expr) => Some(expr),
-                Err(e) => return Err(e),
-            },
-            None => None,
-        };
-        let method = match self.envs.borrow().get_cur_scope_method_decl() {
+    fn get_ir_return(&self, ret: ast::Return, span: Span) -> ir::Return {
+        let val = ret.expr.map(|expr| self.get_ir_expr(*expr));
+        let method = self.envs.borrow().get_cur_scope_method_decl();
+        let method = match method {
             Some(m) => m,
-            None => return Err(vec![SemanticCheckError::InvalidReturn]),
+            None => {
+                self.error(SemanticCheckError::InvalidReturn { span });
+                return ir::Return { method: ir::MethodId(u32::MAX), val };
+            }
         };
-        let method_return_type = method.borrow().return_type.clone();
+
+        let method_return_type = self.ir.borrow()[method].return_type.clone();
         let return_type = ir::ReturnType::to_type(&method_return_type);
         match val {
-            Some(v) if method_return_type != ir::ReturnType::Void && return_type == v.borrow().type_ => Ok(ir::Return {
-                func: method,
-                val: Some(v),
-            }),
-            None if method_return_type == ir::ReturnType::Void => Ok(ir::Return {
-                func: method,
-                val: None,
-            }),
-            _ => Err(vec![SemanticCheckError::ReturnTypeMismatch]),
+            Some(v) if method_return_type == ir::ReturnType::Void => {
+                self.error(SemanticCheckError::UnexpectedReturnValue { span: self.span_of(v) });
+            }
+            Some(v) if !self.has_type(v, return_type) => {
+                self.error(SemanticCheckError::ReturnTypeMismatch {
+                    span: self.span_of(v),
+                    expected: return_type,
+                    actual: self.type_of(v),
+                });
+            }
+            None if method_return_type != ir::ReturnType::Void => {
+                self.error(SemanticCheckError::ReturnTypeMismatch {
+                    span,
+                    expected: return_type,
+                    actual: ir::Type::Void,
+                });
+            }
+            _ => {}
         }
-    }
-
-    fn get_ir_while(&self, whl: ast::While) -> IRResult<ir::While> {
-        let cond = match self.get_ir_expr(whl.expr) {
-            Ok(expr) => expr,
-            Err(e) => return Err(e),
-        };
 
-        let w = create_rc(ir::While0 {
-            cond: cond.clone(),
-            block: None,
-        });
-
-        self.envs.borrow_mut().push(EnvType::While(w.clone()));
+        ir::Return {
+            method,
+            val,
+        }
+    }
 
-        let block = match self.get_ir_block(whl.block, ) {
-            Ok(b) => b,
-            Err(e) => return Err(e),
-        };
+    fn get_ir_while(&self, whl: ast::While, span: Span, scope: ir::VarScope) -> ir::While {
+        let cond = self.get_ir_expr(*whl.expr);
+        self.expect_condition(cond);
 
-        self.envs.borrow_mut().pop();
+        let loop_ = self.ir.borrow_mut().add_loop(ir::Loop { kind: ir::LoopKind::While, span });
+        let block = self.with_scope(EnvType::While(loop_), || self.get_ir_block(whl.block, scope));
 
-        Ok(create_rc(ir::While0 {
+        ir::While {
+            loop_,
             cond,
-            block: Some(block),
-        }))
+            block,
+        }
     }
 }
 
-fn create_rc<T>(x: T) -> Rc<RefCell<T>> {
-    Rc::new(RefCell::new(x))
+fn negated_int_literal(u: &ast::Unary) -> Option<&ast::IntLiteral> {
+    match (&u.op, &u.expr.node) {
+        (ast::UnaryOp::NegInt, ast::Expr_::Literal(ast::Literal::IntLiteral(i))) => Some(i),
+        _ => None,
+    }
 }
@@ -1,26 +1,25 @@
 use std::collections::HashMap;
 
-use super::ir::{ For, While, IfElse, MethodDecl, VarDecl };
-use super::errors::SemanticCheckError;
+use super::ir::{ LoopId, MethodId, VarId };
 
 pub struct EnvStack {
     pub envs: Vec<Env>,
-    pub methods: HashMap<String, MethodDecl>,
+    pub methods: HashMap<String, MethodId>,
 }
 
 pub struct Env {
     pub type_: EnvType,
-    pub table: HashMap<String, VarDecl>,
+    pub table: HashMap<String, VarId>,
 }
 
 #[derive(Debug, Clone)]
 pub enum EnvType {
     Global,
-    Method(MethodDecl),
-    For(For),
-    While(While),
-    If(IfElse),
-    Else(IfElse),
+    Method(MethodId),
+    For(LoopId),
+    While(LoopId),
+    If,
+    Else,
 }
 
 impl EnvStack {
@@ -39,64 +38,74 @@ impl EnvStack {
         self.envs.pop();
     }
 
-    pub fn add_var(&mut self, v: &VarDecl) -> Result<(), SemanticCheckError> {
-        let var = v.clone();
-
-        if var.borrow().arr_len.is_some() && var.borrow().arr_len.unwrap() <= 0 {
-            return Err(SemanticCheckError::ArrayLenShouldPositive(var.borrow().id.clone()));
+    /// Binds `name` in the innermost scope. If it was already declared there
+    /// the earlier declaration is kept and returned.
+    pub fn add_var(&mut self, name: &str, v: VarId) -> Option<VarId> {
+        let table = &mut self.envs.last_mut().unwrap().table;
+        match table.get(name) {
+            Some(prev) => Some(*prev),
+            None => {
+                table.insert(name.to_string(), v);
+                None
+            }
         }
+    }
 
-        println!("{:?}", v.borrow().id.clone());
-        match self.envs.last_mut().unwrap().table.insert(v.borrow().id.clone(), var) {
-            Some(e) => Err(SemanticCheckError::DuplicatedVar(e)),
-            None => Ok(()),
-        }
+    /// Binds an undeclared `name` for the rest of the current method, so
+    /// that it is reported only once.
+    pub fn poison_var(&mut self, name: &str, v: VarId) {
+        let env = self.envs
+            .iter_mut()
+            .rev()
+            .find(|env| matches!(env.type_, EnvType::Method(_) | EnvType::Global))
+            .unwrap();
+        env.table.insert(name.to_string(), v);
     }
 
-    pub fn add_method(&mut self, m: &MethodDecl) -> Result<(), SemanticCheckError> {
-        let method = m.clone();
-        match self.methods.insert(m.borrow().name.clone(), method) {
-            Some(e) => Err(SemanticCheckError::DuplicatedMethod(e)),
-            None => Ok(()),
+    /// Returns the previous method if one with the same name exists, which
+    /// is kept.
+    pub fn add_method(&mut self, name: &str, m: MethodId) -> Option<MethodId> {
+        match self.methods.get(name) {
+            Some(prev) => Some(*prev),
+            None => {
+                self.methods.insert(name.to_string(), m);
+                None
+            }
         }
     }
 
-    pub fn get_var_decl(&self, name: &String) -> Option<VarDecl> {
+    pub fn get_var_decl(&self, name: &str) -> Option<VarId> {
         for env in self.envs.iter().rev() {
-            match env.table.get(name) {
-                Some(v) => return Some(v.clone()),
-                None => (),
-            }
+            if let Some(v) = env.table.get(name) { return Some(*v) }
         }
         None
     }
 
-    pub fn get_method_decl(&self, name: &String) -> Option<MethodDecl> {
-        self.methods.get(name).cloned()
+    pub fn get_global_var_decl(&self, name: &str) -> Option<VarId> {
+        self.envs.first().and_then(|env| env.table.get(name).cloned())
     }
 
-    pub fn get_cur_scope_method_decl(&self) -> Option<MethodDecl> {
-        for env in self.envs.iter().rev() {
-            if let EnvType::Method(m) = &env.type_ {
-                return Some(m.clone());
-            }
-        }
-        None
+    pub fn get_method_decl(&self, name: &str) -> Option<MethodId> {
+        self.methods.get(name).cloned()
     }
 
-    pub fn get_cur_scope_for(&self) -> Option<For> {
+    pub fn get_cur_scope_method_decl(&self) -> Option<MethodId> {
         for env in self.envs.iter().rev() {
-            if let EnvType::For(f) = &env.type_ {
-                return Some(f.clone());
+            if let EnvType::Method(m) = &env.type_ {
+                return Some(*m);
             }
         }
         None
     }
 
-    pub fn get_cur_scope_while(&self) -> Option<While> {
+    /// The innermost loop enclosing the current scope, if any within the
+    /// current method.
+    pub fn get_cur_scope_loop(&self) -> Option<LoopId> {
         for env in self.envs.iter().rev() {
-            if let EnvType::While(w) = &env.type_ {
-                return Some(w.clone());
+            match &env.type_ {
+                EnvType::For(l) | EnvType::While(l) => return Some(*l),
+                EnvType::Method(_) | EnvType::Global => return None,
+                EnvType::If | EnvType::Else => {}
             }
         }
         None
@@ -1,25 +1,165 @@
-use super::ir::{ VarDecl, MethodDecl };
+use crate::ast::Span;
+use crate::diagnostic::Diagnostic;
 
-#[derive(Debug)]
+use super::ir::Type;
+use super::lint::Lint;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SemanticCheckError {
-    DuplicatedVar(VarDecl), // rule 1.1
-    DuplicatedMethod(MethodDecl), // rule 1.1
-    UsedBeforeDeclared(String), // rule 2, 10, 11
-    MainMethodShouldOnlyOne, // rule 3.1
-    MainMethodArgsShouldEmpty, // rule 3.2
-    MainMethodShouldReturnVoid, // rule 3.3
-    ArrayLenShouldPositive(String), // rule 4, 13
-    IdMustArray(String), // rule 12.1
-    TypeOfExprMustInt(String), // rule 12.2
-    MethodSignatureMismatch, // rule 5
-    InvalidMethodArgs, // rule 7
-    InvalidReturn, // ruile 8
-    ReturnTypeMismatch, // rule 6, 9
-    ConditionTypeShouldBool, // rule 14
-    OperandsTypeMismatch, // rule 15, 16, 17
-    LocationTypeMismatch, // rule 18, 19
-    InvalidBreak, // rule 20.1
-    InvalidContinue, // rule 20.2
+    DuplicatedVar { name: String, span: Span, prev: Span }, // rule 1
+    DuplicatedMethod { name: String, span: Span, prev: Span }, // rule 1
+    UsedBeforeDeclared { name: String, span: Span }, // rule 2, 10, 11
+    MainMethodShouldOnlyOne { spans: Vec<Span> }, // rule 3
+    MainMethodArgsShouldEmpty { span: Span }, // rule 3
+    MainMethodShouldReturnVoid { span: Span }, // rule 3
+    ArrayLenShouldPositive { name: String, span: Span }, // rule 4
+    MethodSignatureMismatch { name: String, span: Span, expected: usize, actual: usize }, // rule 5
+    InvalidMethodArgs { name: String, span: Span, expected: Type, actual: Type }, // rule 5
+    MethodMustReturnValue { name: String, span: Span }, // rule 6
+    ArrayAsMethodArg { name: String, span: Span }, // rule 7
+    UnexpectedReturnValue { span: Span }, // rule 8
+    ReturnTypeMismatch { span: Span, expected: Type, actual: Type }, // rule 9
+    NotAVariable { name: String, span: Span }, // rule 10
+    NotAMethod { name: String, span: Span }, // rule 11
+    LocationTypeMismatch { name: String, span: Span, is_array: bool }, // rule 12.1, and arrays used as scalars
+    TypeOfExprMustInt { name: String, span: Span, actual: Type }, // rule 12.2
+    IdMustArray { name: String, span: Span }, // rule 13
+    ConditionTypeShouldBool { span: Span, actual: Type }, // rule 14
+    OperandsTypeMismatch { span: Span, expected: Type, actual: Type }, // rule 15, 16, 17, 18, 19
+    InvalidBreak { span: Span }, // rule 20
+    InvalidContinue { span: Span }, // rule 20
+    IntLiteralOutOfRange { literal: String, span: Span }, // rule 21, with 32-bit `int`s
+    InvalidLoopVar { name: String, span: Span }, // the index of a `for` must be a scalar `int`
+    InvalidReturn { span: Span }, // `return` outside of a method
+}
+
+/// Problems that don't stop compilation, each reported under a `Lint`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SemanticCheckWarning {
+    UnusedVariable { name: String, span: Span },
+    UnusedMethod { name: String, span: Span },
+    Shadowing { name: String, span: Span, shadowed: Span }, // a local or argument hiding a global or argument
+    LoopVarAssign { name: String, span: Span }, // assignment to the variable of an enclosing `for`
+    SelfAssign { name: String, span: Span },
+    ConstantCondition { span: Span },
+    UnreachableCode { span: Span, cause: Span },
+    MissingReturn { name: String, span: Span }, // a non-`void` method that can fall off its end
 }
 
 pub type IRResult<T> = Result<T, Vec<SemanticCheckError>>;
+
+fn mismatch(expected: &Type, actual: &Type) -> String {
+    format!("expected `{}`, found `{}`", expected, actual)
+}
+
+impl SemanticCheckError {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            Self::DuplicatedVar { name, span, prev } => Diagnostic::error(format!("`{}` is declared twice in the same scope", name))
+                .with_label(*span, "redeclared here")
+                .with_secondary_label(*prev, format!("previous declaration of `{}`", name)),
+            Self::DuplicatedMethod { name, span, prev } => Diagnostic::error(format!("method `{}` is declared twice", name))
+                .with_label(*span, "redeclared here")
+                .with_secondary_label(*prev, format!("previous declaration of `{}`", name)),
+            Self::UsedBeforeDeclared { name, span } => Diagnostic::error(format!("cannot find `{}` in this scope", name))
+                .with_label(*span, "not declared before use"),
+            Self::MainMethodShouldOnlyOne { spans } if spans.is_empty() => Diagnostic::error("no `main` method")
+                .with_note("every program must declare `void main()`"),
+            Self::MainMethodShouldOnlyOne { spans } => {
+                let mut d = Diagnostic::error("`main` is declared more than once");
+                for span in spans {
+                    d = d.with_label(*span, "");
+                }
+                d
+            }
+            Self::MainMethodArgsShouldEmpty { span } => Diagnostic::error("`main` must not take arguments")
+                .with_label(*span, ""),
+            Self::MainMethodShouldReturnVoid { span } => Diagnostic::error("`main` must return `void`")
+                .with_label(*span, ""),
+            Self::ArrayLenShouldPositive { name, span } => Diagnostic::error(format!("array `{}` must have a positive length", name))
+                .with_label(*span, ""),
+            Self::IdMustArray { name, span } => Diagnostic::error(format!("`len` applied to `{}`, which is not an array", name))
+                .with_label(*span, "not an array"),
+            Self::TypeOfExprMustInt { name, span, actual } => Diagnostic::error(format!("index of array `{}` must be an `int`", name))
+                .with_label(*span, mismatch(&Type::Int, actual)),
+            Self::MethodSignatureMismatch { name, span, expected, actual } => Diagnostic::error(format!("wrong number of arguments to `{}`", name))
+                .with_label(*span, format!("expected {} argument(s), found {}", expected, actual)),
+            Self::InvalidMethodArgs { name, span, expected, actual } => Diagnostic::error(format!("mismatched argument type in call to `{}`", name))
+                .with_label(*span, mismatch(expected, actual)),
+            Self::MethodMustReturnValue { name, span } => Diagnostic::error(format!("`{}` does not return a value", name))
+                .with_label(*span, "used as an expression")
+                .with_note("only methods that return `int` or `bool` can be used in an expression"),
+            Self::ArrayAsMethodArg { name, span } => Diagnostic::error(format!("array `{}` passed to a method", name))
+                .with_label(*span, "")
+                .with_note("pass an element such as `a[0]` instead"),
+            Self::UnexpectedReturnValue { span } => Diagnostic::error("`return` with a value in a method returning `void`")
+                .with_label(*span, ""),
+            Self::NotAVariable { name, span } => Diagnostic::error(format!("`{}` is an imported method, not a variable", name))
+                .with_label(*span, ""),
+            Self::NotAMethod { name, span } => Diagnostic::error(format!("`{}` is a variable, not a method", name))
+                .with_label(*span, "called here"),
+            Self::InvalidLoopVar { name, span } => Diagnostic::error(format!("loop variable `{}` must be a scalar `int`", name))
+                .with_label(*span, ""),
+            Self::InvalidReturn { span } => Diagnostic::error("`return` outside of a method")
+                .with_label(*span, ""),
+            Self::ReturnTypeMismatch { span, expected, actual } => Diagnostic::error("mismatched return type")
+                .with_label(*span, mismatch(expected, actual)),
+            Self::ConditionTypeShouldBool { span, actual } => Diagnostic::error("condition must be a `bool`")
+                .with_label(*span, mismatch(&Type::Bool, actual)),
+            Self::OperandsTypeMismatch { span, expected, actual } => Diagnostic::error("mismatched types")
+                .with_label(*span, mismatch(expected, actual)),
+            Self::LocationTypeMismatch { name, span, is_array: true } => Diagnostic::error(format!("array `{}` used without an index", name))
+                .with_label(*span, ""),
+            Self::LocationTypeMismatch { name, span, is_array: false } => Diagnostic::error(format!("`{}` is not an array and cannot be indexed", name))
+                .with_label(*span, ""),
+            Self::InvalidBreak { span } => Diagnostic::error("`break` outside of a loop")
+                .with_label(*span, ""),
+            Self::InvalidContinue { span } => Diagnostic::error("`continue` outside of a loop")
+                .with_label(*span, ""),
+            Self::IntLiteralOutOfRange { literal, span } => Diagnostic::error(format!("integer literal `{}` is out of range for `int`", literal))
+                .with_label(*span, "")
+                .with_note(format!("the range of `int` is {} to {}", i32::MIN, i32::MAX)),
+        }
+    }
+}
+
+impl SemanticCheckWarning {
+    pub fn lint(&self) -> Lint {
+        match self {
+            Self::UnusedVariable { .. } => Lint::UnusedVariable,
+            Self::UnusedMethod { .. } => Lint::UnusedMethod,
+            Self::Shadowing { .. } => Lint::Shadowing,
+            Self::LoopVarAssign { .. } => Lint::LoopVarAssign,
+            Self::SelfAssign { .. } => Lint::SelfAssign,
+            Self::ConstantCondition { .. } => Lint::ConstantCondition,
+            Self::UnreachableCode { .. } => Lint::UnreachableCode,
+            Self::MissingReturn { .. } => Lint::MissingReturn,
+        }
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let d = match self {
+            Self::UnusedVariable { name, span } => Diagnostic::warning(format!("variable `{}` is never read", name))
+                .with_label(*span, ""),
+            Self::UnusedMethod { name, span } => Diagnostic::warning(format!("method `{}` is never called", name))
+                .with_label(*span, ""),
+            Self::Shadowing { name, span, shadowed } => Diagnostic::warning(format!("`{}` shadows an earlier declaration", name))
+                .with_label(*span, "")
+                .with_secondary_label(*shadowed, format!("`{}` declared here", name)),
+            Self::LoopVarAssign { name, span } => Diagnostic::warning(format!("assignment to loop variable `{}`", name))
+                .with_label(*span, "")
+                .with_note("the loop updates its variable itself; changing it in the body is easy to get wrong"),
+            Self::SelfAssign { name, span } => Diagnostic::warning(format!("`{}` is assigned to itself", name))
+                .with_label(*span, ""),
+            Self::ConstantCondition { span } => Diagnostic::warning("condition is constant")
+                .with_label(*span, "does not depend on any variable"),
+            Self::MissingReturn { name, span } => Diagnostic::warning(format!("`{}` may reach its end without returning a value", name))
+                .with_label(*span, "")
+                .with_note("falling off the end of a method that returns a value aborts the program at runtime"),
+            Self::UnreachableCode { span, cause } => Diagnostic::warning("unreachable code")
+                .with_label(*span, "")
+                .with_secondary_label(*cause, "any code following this is unreachable"),
+        };
+        d.with_note(format!("`-A {}` turns this warning off", self.lint().name()))
+    }
+}
@@ -0,0 +1,128 @@
+//! Reachability over the checked IR: finds statements that can never run
+//! and non-`void` methods that can reach the end of their body.
+//!
+//! Only `return`, `break` and `continue` end a path; conditions are not
+//! evaluated, except that a literal `true` or `false` picks its branch, so
+//! that `while (true) { ... return x; }` is known not to fall through. The
+//! branch that is not picked is still checked for unreachable code.
+
+use std::collections::HashSet;
+
+use super::errors::SemanticCheckWarning;
+use super::ir::{ self, IRRoot, LoopId, StmtId };
+
+/// Sets `falls_off` on every method of `ir`, and returns the warnings found.
+pub fn check_flow(ir: &mut IRRoot) -> Vec<SemanticCheckWarning> {
+    let mut flow = Flow {
+        ir,
+        broken: HashSet::new(),
+        warnings: Vec::new(),
+    };
+
+    let mut falls_off = Vec::new();
+    for &method in &flow.ir.root.method_decls {
+        let decl = &flow.ir[method];
+        if flow.block(&decl.block) && decl.return_type != ir::ReturnType::Void {
+            flow.warnings.push(SemanticCheckWarning::MissingReturn { name: decl.name.clone(), span: decl.span });
+            falls_off.push(method);
+        }
+    }
+
+    let warnings = flow.warnings;
+    for method in falls_off {
+        ir[method].falls_off = true;
+    }
+    warnings
+}
+
+struct Flow<'a> {
+    ir: &'a IRRoot,
+    // loops with a reachable `break`
+    broken: HashSet<LoopId>,
+    warnings: Vec<SemanticCheckWarning>,
+}
+
+impl Flow<'_> {
+    // whether the end of `block` can be reached from its start
+    fn block(&mut self, block: &ir::Block) -> bool {
+        for (i, &stmt) in block.statements.iter().enumerate() {
+            if !self.statement(stmt) {
+                let dead = &block.statements[i + 1..];
+                if let (Some(&first), Some(&last)) = (dead.first(), dead.last()) {
+                    self.warnings.push(SemanticCheckWarning::UnreachableCode {
+                        span: self.ir[first].span.to(self.ir[last].span),
+                        cause: self.ir[stmt].span,
+                    });
+                }
+                return false;
+            }
+        }
+        true
+    }
+
+    fn statement(&mut self, stmt: StmtId) -> bool {
+        let ir = self.ir;
+        match &ir[stmt].stmt {
+            ir::StmtType::Assign(_) | ir::StmtType::MethodCall(_) => true,
+            ir::StmtType::Return(_) | ir::StmtType::Continue(_) => false,
+            ir::StmtType::Break(loop_) => {
+                self.broken.insert(*loop_);
+                false
+            }
+            ir::StmtType::IfElse(if_else) => {
+                let else_block = |flow: &mut Self| if_else.else_block.as_ref().is_none_or(|b| flow.block(b));
+                match literal_bool(ir, if_else.cond) {
+                    Some(true) => {
+                        if let Some(b) = &if_else.else_block {
+                            self.dead_block(b);
+                        }
+                        self.block(&if_else.if_block)
+                    }
+                    Some(false) => {
+                        self.dead_block(&if_else.if_block);
+                        else_block(self)
+                    }
+                    None => {
+                        let if_completes = self.block(&if_else.if_block);
+                        let else_completes = else_block(self);
+                        if_completes || else_completes
+                    }
+                }
+            }
+            ir::StmtType::For(for_) => self.loop_(for_.loop_, for_.cond, &for_.block),
+            ir::StmtType::While(while_) => self.loop_(while_.loop_, while_.cond, &while_.block),
+        }
+    }
+
+    // looks for unreachable code in a block that never runs, whose `break`s
+    // don't count
+    fn dead_block(&mut self, block: &ir::Block) {
+        let broken = self.broken.clone();
+        self.block(block);
+        self.broken = broken;
+    }
+
+    fn loop_(&mut self, loop_: LoopId, cond: ir::ExprId, block: &ir::Block) -> bool {
+        match literal_bool(self.ir, cond) {
+            Some(false) => {
+                self.dead_block(block);
+                true
+            }
+            Some(true) => {
+                self.block(block);
+                self.broken.contains(&loop_)
+            }
+            None => {
+                self.block(block);
+                true
+            }
+        }
+    }
+}
+
+fn literal_bool(ir: &IRRoot, expr: ir::ExprId) -> Option<bool> {
+    match ir[expr].expr {
+        ir::ExprType::Literal(ir::Literal::BoolLiteral(b)) => Some(b),
+        _ => None,
+    }
+}
@@ -0,0 +1,471 @@
+use crate::ast::{ self, Span };
+use std::fmt;
+use std::ops::{ Index, IndexMut };
+
+pub mod visit;
+
+/// Defines a typed index into one of the arenas of `IRRoot`.
+macro_rules! id_type {
+    ( $name:ident ) => {
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name(pub u32);
+
+        impl $name {
+            pub fn index(self) -> usize {
+                self.0 as usize
+            }
+        }
+    };
+}
+
+pub(crate) use id_type;
+
+id_type!(VarId);
+id_type!(MethodId);
+id_type!(StmtId);
+id_type!(ExprId);
+id_type!(LoopId);
+
+/// The checked program. Nodes live in flat arenas and refer to each other by
+/// ID, so the whole tree is plain data: it can be compared, hashed and sent
+/// across threads, and passes never have to juggle borrows.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct IRRoot {
+    pub root: ProgramDecl,
+    /// symbol table of every variable, import and argument
+    pub vars: Vec<VarDecl>,
+    pub methods: Vec<MethodDecl>,
+    pub stmts: Vec<Statement>,
+    pub exprs: Vec<Expr>,
+    pub loops: Vec<Loop>,
+}
+
+impl IRRoot {
+    pub fn add_var(&mut self, var: VarDecl) -> VarId {
+        self.vars.push(var);
+        VarId(self.vars.len() as u32 - 1)
+    }
+
+    pub fn add_method(&mut self, method: MethodDecl) -> MethodId {
+        self.methods.push(method);
+        MethodId(self.methods.len() as u32 - 1)
+    }
+
+    pub fn add_stmt(&mut self, stmt: Statement) -> StmtId {
+        self.stmts.push(stmt);
+        StmtId(self.stmts.len() as u32 - 1)
+    }
+
+    pub fn add_expr(&mut self, expr: Expr) -> ExprId {
+        self.exprs.push(expr);
+        ExprId(self.exprs.len() as u32 - 1)
+    }
+
+    pub fn add_loop(&mut self, loop_: Loop) -> LoopId {
+        self.loops.push(loop_);
+        LoopId(self.loops.len() as u32 - 1)
+    }
+}
+
+macro_rules! impl_index {
+    ( $id:ident, $node:ident, $arena:ident ) => {
+        impl Index<$id> for IRRoot {
+            type Output = $node;
+
+            fn index(&self, id: $id) -> &$node {
+                &self.$arena[id.index()]
+            }
+        }
+
+        impl IndexMut<$id> for IRRoot {
+            fn index_mut(&mut self, id: $id) -> &mut $node {
+                &mut self.$arena[id.index()]
+            }
+        }
+    };
+}
+
+impl_index!(VarId, VarDecl, vars);
+impl_index!(MethodId, MethodDecl, methods);
+impl_index!(StmtId, Statement, stmts);
+impl_index!(ExprId, Expr, exprs);
+impl_index!(LoopId, Loop, loops);
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct ProgramDecl {
+    pub import_decls: Vec<VarId>,
+    pub field_decls: Vec<VarId>,
+    pub method_decls: Vec<MethodId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VarDecl {
+    pub type_: Type,
+    pub name: Id,
+    pub arr_len: Option<i32>,
+    pub scope: VarScope,
+    pub span: Span,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum VarScope {
+    Import,
+    Global,
+    /// an argument or a local of the method
+    Method(MethodId),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodDecl {
+    pub return_type: ReturnType,
+    pub name: Id,
+    pub args: Vec<VarId>,
+    pub block: Block,
+    /// whether a non-`void` method can reach the end of its body without
+    /// returning, which is a runtime error; set by `check_flow`
+    pub falls_off: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ReturnType {
+    Type(Type),
+    Void,
+}
+
+impl ReturnType {
+    pub fn from(t: &ast::ReturnType) -> Self {
+        match t {
+            ast::ReturnType::Type(tt) => Self::Type(Type::from(tt)),
+            ast::ReturnType::Void => Self::Void,
+        }
+    }
+
+    pub fn to_type(t: &ReturnType) -> Type {
+        match t {
+            ReturnType::Type(tt) => tt.to_owned(),
+            ReturnType::Void => Type::Void,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Type {
+    Int,
+    Bool,
+    Void,
+    /// the type of an expression that failed to check; checks involving it
+    /// are skipped so that one mistake is reported only once
+    Error,
+}
+
+impl Type {
+    pub fn from(t: &ast::Type) -> Self {
+        match t {
+            ast::Type::Int => Self::Int,
+            ast::Type::Bool => Self::Bool,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Type::Int => "int",
+            Type::Bool => "bool",
+            Type::Void => "void",
+            Type::Error => "{unknown}",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct Block {
+    pub field_decls: Vec<VarId>,
+    pub statements: Vec<StmtId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Statement {
+    pub stmt: StmtType,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum StmtType {
+    Assign(Assign),
+    MethodCall(MethodCall),
+    IfElse(IfElse),
+    For(For),
+    While(While),
+    Return(Return),
+    Break(LoopId),
+    Continue(LoopId),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Assign {
+    pub dst: Location,
+    pub assign_expr: AssignExpr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Location {
+    pub var: VarId,
+    pub index: Option<ExprId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AssignExpr {
+    AssignOpExpr(AssignOpExpr),
+    Increment(Increment),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AssignOpExpr {
+    pub assign_op: AssignOp,
+    pub expr: ExprId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AssignOp {
+    Assign,
+    CompoundAssignOp(CompoundAssignOp),
+}
+
+impl AssignOp {
+    pub fn from(t: &ast::AssignOp) -> Self {
+        match t {
+            ast::AssignOp::Assign => Self::Assign,
+            ast::AssignOp::CompoundAssignOp(tt) => Self::CompoundAssignOp(CompoundAssignOp::from(tt)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CompoundAssignOp {
+    AddAssign,
+    SubAssign,
+}
+
+impl CompoundAssignOp {
+    pub fn from(t: &ast::CompoundAssignOp) -> Self {
+        match t {
+            ast::CompoundAssignOp::AddAssign => Self::AddAssign,
+            ast::CompoundAssignOp::SubAssign => Self::SubAssign,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Increment {
+    SelfAdd,
+    SelfSub,
+}
+
+impl Increment {
+    pub fn from(i: &ast::Increment) -> Self {
+        match i {
+            ast::Increment::SelfAdd => Self::SelfAdd,
+            ast::Increment::SelfSub => Self::SelfSub,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MethodCall {
+    Method(MethodCall0),
+    Callout(MethodCall1),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodCall0 {
+    pub method: MethodId,
+    pub args: Vec<ExprId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodCall1 {
+    pub name: String,
+    pub args: Vec<ImportArg>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ImportArg {
+    Expr(ExprId),
+    StringLiteral(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IfElse {
+    pub cond: ExprId,
+    pub if_block: Block,
+    pub else_block: Option<Block>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct For {
+    pub loop_: LoopId,
+    pub var: VarId,
+    pub init_expr: ExprId,
+    pub cond: ExprId,
+    pub update: ForUpdate,
+    pub block: Block,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ForUpdate {
+    pub dst: Location,
+    pub update_expr: ForUpdateExpr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ForUpdateExpr {
+    AssignExpr(ForUpdateAssignExpr),
+    Increment(Increment),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ForUpdateAssignExpr {
+    pub compound_assign_op: CompoundAssignOp,
+    pub expr: ExprId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct While {
+    pub loop_: LoopId,
+    pub cond: ExprId,
+    pub block: Block,
+}
+
+/// A `for` or `while` loop, the target of `break` and `continue`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Loop {
+    pub kind: LoopKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum LoopKind {
+    For,
+    While,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Return {
+    pub method: MethodId,
+    pub val: Option<ExprId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Expr {
+    pub type_: Type,
+    pub expr: ExprType,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ExprType {
+    Location(Location),
+    MethodCall(MethodCall),
+    Literal(Literal),
+    LenId(VarId),
+    Unary(Unary),
+    Binary(Binary),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Unary {
+    pub expr: ExprId,
+    pub op: UnaryOp,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum UnaryOp {
+    NegInt,
+    NegBool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Binary {
+    pub lhs: ExprId,
+    pub rhs: ExprId,
+    pub op: BinaryOp,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    // ArithOp
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    // RelOp
+    GT,
+    LT,
+    LE,
+    GE,
+    // EqOp
+    EQ,
+    NE,
+    // CondOp
+    And,
+    Or,
+}
+
+impl BinaryOp {
+    pub fn from(t: &ast::BinaryOp) -> Self {
+        match t {
+            ast::BinaryOp::ArithOp(a) => match a {
+                ast::ArithOp::Add => BinaryOp::Add,
+                ast::ArithOp::Sub => BinaryOp::Sub,
+                ast::ArithOp::Mul => BinaryOp::Mul,
+                ast::ArithOp::Div => BinaryOp::Div,
+                ast::ArithOp::Mod => BinaryOp::Mod,
+            },
+            ast::BinaryOp::RelOp(a) => match a {
+                ast::RelOp::Greater => BinaryOp::GT,
+                ast::RelOp::GreaterEq => BinaryOp::GE,
+                ast::RelOp::Less => BinaryOp::LT,
+                ast::RelOp::LessEq => BinaryOp::LE,
+            },
+            ast::BinaryOp::EqOp(a) => match a {
+                ast::EqOp::EQ => BinaryOp::EQ,
+                ast::EqOp::NE => BinaryOp::NE,
+            },
+            ast::BinaryOp::CondOp(a) => match a {
+                ast::CondOp::Or => BinaryOp::Or,
+                ast::CondOp::And => BinaryOp::And,
+            },
+        }
+    }
+
+    pub fn get_return_type(&self) -> Type {
+        match self {
+            Self::Or  => Type::Bool, // logical or
+            Self::And => Type::Bool, // logical and
+            Self::EQ  => Type::Bool, // ==
+            Self::NE  => Type::Bool, // !=
+            Self::GT  => Type::Bool, // >
+            Self::LT  => Type::Bool, // <
+            Self::GE  => Type::Bool, // >=
+            Self::LE  => Type::Bool, // <=
+            Self::Add => Type::Int, // +
+            Self::Sub => Type::Int, // -
+            Self::Mul => Type::Int, // *
+            Self::Div => Type::Int, // /
+            Self::Mod => Type::Int, // %
+        } 
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Literal {
+    IntLiteral(i32),
+    CharLiteral(char),
+    BoolLiteral(bool),
+}
+
+pub type Id = String;
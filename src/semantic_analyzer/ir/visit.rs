@@ -0,0 +1,464 @@
+//! Traversal of the checked IR.
+//!
+//! `IrVisitor` walks blocks, statements and expressions in evaluation order:
+//! an assignment's destination index before its value, a `for` loop's
+//! variable (as the destination of its initializer, since the loop declares
+//! no variable of its own), initializer, condition, body and then update, and
+//! operands left to right.
+//!
+//! Nodes refer to each other by ID, so every method is handed the `IRRoot`
+//! to look them up in; a visitor may freely look at other nodes (e.g. the
+//! loop a `break` refers to) while it runs.
+
+use std::mem;
+
+use super::*;
+
+pub trait IrVisitor {
+    fn visit_program(&mut self, ir: &IRRoot) {
+        walk_program(self, ir)
+    }
+
+    fn visit_var_decl(&mut self, _ir: &IRRoot, _var: VarId) {}
+
+    fn visit_method_decl(&mut self, ir: &IRRoot, method: MethodId) {
+        walk_method_decl(self, ir, method)
+    }
+
+    fn visit_block(&mut self, ir: &IRRoot, block: &Block) {
+        walk_block(self, ir, block)
+    }
+
+    fn visit_statement(&mut self, ir: &IRRoot, statement: StmtId) {
+        walk_statement(self, ir, statement)
+    }
+
+    fn visit_assign(&mut self, ir: &IRRoot, assign: &Assign) {
+        walk_assign(self, ir, assign)
+    }
+
+    fn visit_if_else(&mut self, ir: &IRRoot, if_else: &IfElse) {
+        walk_if_else(self, ir, if_else)
+    }
+
+    fn visit_for(&mut self, ir: &IRRoot, for_: &For) {
+        walk_for(self, ir, for_)
+    }
+
+    fn visit_for_update(&mut self, ir: &IRRoot, update: &ForUpdate) {
+        walk_for_update(self, ir, update)
+    }
+
+    fn visit_while(&mut self, ir: &IRRoot, while_: &While) {
+        walk_while(self, ir, while_)
+    }
+
+    fn visit_return(&mut self, ir: &IRRoot, return_: &Return) {
+        walk_return(self, ir, return_)
+    }
+
+    fn visit_break(&mut self, _ir: &IRRoot, _loop: LoopId) {}
+
+    fn visit_continue(&mut self, _ir: &IRRoot, _loop: LoopId) {}
+
+    fn visit_method_call(&mut self, ir: &IRRoot, call: &MethodCall) {
+        walk_method_call(self, ir, call)
+    }
+
+    fn visit_location(&mut self, ir: &IRRoot, location: &Location) {
+        walk_location(self, ir, location)
+    }
+
+    fn visit_expr(&mut self, ir: &IRRoot, expr: ExprId) {
+        walk_expr(self, ir, expr)
+    }
+}
+
+pub fn walk_program<V: IrVisitor + ?Sized>(v: &mut V, ir: &IRRoot) {
+    for &var in ir.root.import_decls.iter().chain(&ir.root.field_decls) {
+        v.visit_var_decl(ir, var);
+    }
+    for &method in &ir.root.method_decls {
+        v.visit_method_decl(ir, method);
+    }
+}
+
+pub fn walk_method_decl<V: IrVisitor + ?Sized>(v: &mut V, ir: &IRRoot, method: MethodId) {
+    for &arg in &ir[method].args {
+        v.visit_var_decl(ir, arg);
+    }
+    v.visit_block(ir, &ir[method].block);
+}
+
+pub fn walk_block<V: IrVisitor + ?Sized>(v: &mut V, ir: &IRRoot, block: &Block) {
+    for &var in &block.field_decls {
+        v.visit_var_decl(ir, var);
+    }
+    for &statement in &block.statements {
+        v.visit_statement(ir, statement);
+    }
+}
+
+pub fn walk_statement<V: IrVisitor + ?Sized>(v: &mut V, ir: &IRRoot, statement: StmtId) {
+    match &ir[statement].stmt {
+        StmtType::Assign(a) => v.visit_assign(ir, a),
+        StmtType::MethodCall(m) => v.visit_method_call(ir, m),
+        StmtType::IfElse(i) => v.visit_if_else(ir, i),
+        StmtType::For(f) => v.visit_for(ir, f),
+        StmtType::While(w) => v.visit_while(ir, w),
+        StmtType::Return(r) => v.visit_return(ir, r),
+        StmtType::Break(l) => v.visit_break(ir, *l),
+        StmtType::Continue(l) => v.visit_continue(ir, *l),
+    }
+}
+
+pub fn walk_assign<V: IrVisitor + ?Sized>(v: &mut V, ir: &IRRoot, assign: &Assign) {
+    v.visit_location(ir, &assign.dst);
+    if let AssignExpr::AssignOpExpr(e) = &assign.assign_expr {
+        v.visit_expr(ir, e.expr);
+    }
+}
+
+pub fn walk_if_else<V: IrVisitor + ?Sized>(v: &mut V, ir: &IRRoot, if_else: &IfElse) {
+    v.visit_expr(ir, if_else.cond);
+    v.visit_block(ir, &if_else.if_block);
+    if let Some(b) = &if_else.else_block {
+        v.visit_block(ir, b);
+    }
+}
+
+pub fn walk_for<V: IrVisitor + ?Sized>(v: &mut V, ir: &IRRoot, for_: &For) {
+    v.visit_location(ir, &Location { var: for_.var, index: None });
+    v.visit_expr(ir, for_.init_expr);
+    v.visit_expr(ir, for_.cond);
+    v.visit_block(ir, &for_.block);
+    v.visit_for_update(ir, &for_.update);
+}
+
+pub fn walk_for_update<V: IrVisitor + ?Sized>(v: &mut V, ir: &IRRoot, update: &ForUpdate) {
+    v.visit_location(ir, &update.dst);
+    if let ForUpdateExpr::AssignExpr(e) = &update.update_expr {
+        v.visit_expr(ir, e.expr);
+    }
+}
+
+pub fn walk_while<V: IrVisitor + ?Sized>(v: &mut V, ir: &IRRoot, while_: &While) {
+    v.visit_expr(ir, while_.cond);
+    v.visit_block(ir, &while_.block);
+}
+
+pub fn walk_return<V: IrVisitor + ?Sized>(v: &mut V, ir: &IRRoot, return_: &Return) {
+    if let Some(e) = return_.val {
+        v.visit_expr(ir, e);
+    }
+}
+
+pub fn walk_method_call<V: IrVisitor + ?Sized>(v: &mut V, ir: &IRRoot, call: &MethodCall) {
+    match call {
+        MethodCall::Method(m) => {
+            for &arg in &m.args {
+                v.visit_expr(ir, arg);
+            }
+        }
+        MethodCall::Callout(c) => {
+            for arg in &c.args {
+                if let ImportArg::Expr(e) = arg {
+                    v.visit_expr(ir, *e);
+                }
+            }
+        }
+    }
+}
+
+pub fn walk_location<V: IrVisitor + ?Sized>(v: &mut V, ir: &IRRoot, location: &Location) {
+    if let Some(index) = location.index {
+        v.visit_expr(ir, index);
+    }
+}
+
+pub fn walk_expr<V: IrVisitor + ?Sized>(v: &mut V, ir: &IRRoot, expr: ExprId) {
+    match &ir[expr].expr {
+        ExprType::Location(l) => v.visit_location(ir, l),
+        ExprType::MethodCall(m) => v.visit_method_call(ir, m),
+        ExprType::Literal(_) | ExprType::LenId(_) => {}
+        ExprType::Unary(u) => v.visit_expr(ir, u.expr),
+        ExprType::Binary(b) => {
+            v.visit_expr(ir, b.lhs);
+            v.visit_expr(ir, b.rhs);
+        }
+    }
+}
+
+/// Rewrites the IR bottom-up.
+///
+/// Each `fold_*` method takes a node and returns its replacement; the
+/// defaults (`fold_*_children`) fold the children in evaluation order and
+/// return the node itself. Statements fold to a list so that passes can
+/// delete or expand them. Nodes that are referred to by ID are updated in
+/// place in the arena, and a folder may add new ones.
+pub trait IrFolder {
+    fn fold_program(&mut self, ir: &mut IRRoot) {
+        fold_program_children(self, ir)
+    }
+
+    fn fold_method_decl(&mut self, ir: &mut IRRoot, method: MethodId) {
+        fold_method_decl_children(self, ir, method)
+    }
+
+    fn fold_block(&mut self, ir: &mut IRRoot, block: Block) -> Block {
+        fold_block_children(self, ir, block)
+    }
+
+    fn fold_statement(&mut self, ir: &mut IRRoot, statement: StmtId) -> Vec<StmtId> {
+        vec![fold_statement_children(self, ir, statement)]
+    }
+
+    fn fold_location(&mut self, ir: &mut IRRoot, location: Location) -> Location {
+        fold_location_children(self, ir, location)
+    }
+
+    fn fold_expr(&mut self, ir: &mut IRRoot, expr: ExprId) -> ExprId {
+        fold_expr_children(self, ir, expr)
+    }
+}
+
+pub fn fold_program_children<F: IrFolder + ?Sized>(f: &mut F, ir: &mut IRRoot) {
+    for method in ir.root.method_decls.clone() {
+        f.fold_method_decl(ir, method);
+    }
+}
+
+pub fn fold_method_decl_children<F: IrFolder + ?Sized>(f: &mut F, ir: &mut IRRoot, method: MethodId) {
+    let block = mem::take(&mut ir[method].block);
+    ir[method].block = f.fold_block(ir, block);
+}
+
+pub fn fold_block_children<F: IrFolder + ?Sized>(f: &mut F, ir: &mut IRRoot, block: Block) -> Block {
+    Block {
+        field_decls: block.field_decls,
+        statements: block.statements.into_iter().flat_map(|s| f.fold_statement(ir, s)).collect(),
+    }
+}
+
+pub fn fold_statement_children<F: IrFolder + ?Sized>(f: &mut F, ir: &mut IRRoot, statement: StmtId) -> StmtId {
+    // children are referred to by ID, so this copy is shallow
+    let folded = match ir[statement].stmt.clone() {
+        StmtType::Assign(a) => StmtType::Assign(fold_assign(f, ir, a)),
+        StmtType::MethodCall(m) => StmtType::MethodCall(fold_method_call(f, ir, m)),
+        StmtType::IfElse(i) => StmtType::IfElse(IfElse {
+            cond: f.fold_expr(ir, i.cond),
+            if_block: f.fold_block(ir, i.if_block),
+            else_block: i.else_block.map(|b| f.fold_block(ir, b)),
+        }),
+        StmtType::For(l) => {
+            let init_expr = f.fold_expr(ir, l.init_expr);
+            let cond = f.fold_expr(ir, l.cond);
+            let block = f.fold_block(ir, l.block);
+            let update = fold_for_update(f, ir, l.update);
+            StmtType::For(For { init_expr, cond, block, update, ..l })
+        }
+        StmtType::While(w) => {
+            let cond = f.fold_expr(ir, w.cond);
+            let block = f.fold_block(ir, w.block);
+            StmtType::While(While { cond, block, ..w })
+        }
+        StmtType::Return(r) => StmtType::Return(Return { val: r.val.map(|e| f.fold_expr(ir, e)), ..r }),
+        s @ (StmtType::Break(_) | StmtType::Continue(_)) => s,
+    };
+    ir[statement].stmt = folded;
+    statement
+}
+
+fn fold_assign<F: IrFolder + ?Sized>(f: &mut F, ir: &mut IRRoot, assign: Assign) -> Assign {
+    let dst = f.fold_location(ir, assign.dst);
+    let assign_expr = match assign.assign_expr {
+        AssignExpr::AssignOpExpr(e) => AssignExpr::AssignOpExpr(AssignOpExpr {
+            assign_op: e.assign_op,
+            expr: f.fold_expr(ir, e.expr),
+        }),
+        AssignExpr::Increment(i) => AssignExpr::Increment(i),
+    };
+    Assign { dst, assign_expr }
+}
+
+fn fold_for_update<F: IrFolder + ?Sized>(f: &mut F, ir: &mut IRRoot, update: ForUpdate) -> ForUpdate {
+    let dst = f.fold_location(ir, update.dst);
+    let update_expr = match update.update_expr {
+        ForUpdateExpr::AssignExpr(e) => ForUpdateExpr::AssignExpr(ForUpdateAssignExpr {
+            compound_assign_op: e.compound_assign_op,
+            expr: f.fold_expr(ir, e.expr),
+        }),
+        ForUpdateExpr::Increment(i) => ForUpdateExpr::Increment(i),
+    };
+    ForUpdate { dst, update_expr }
+}
+
+fn fold_method_call<F: IrFolder + ?Sized>(f: &mut F, ir: &mut IRRoot, call: MethodCall) -> MethodCall {
+    match call {
+        MethodCall::Method(m) => MethodCall::Method(MethodCall0 {
+            method: m.method,
+            args: m.args.into_iter().map(|a| f.fold_expr(ir, a)).collect(),
+        }),
+        MethodCall::Callout(c) => MethodCall::Callout(MethodCall1 {
+            name: c.name,
+            args: c.args.into_iter().map(|a| match a {
+                ImportArg::Expr(e) => ImportArg::Expr(f.fold_expr(ir, e)),
+                s @ ImportArg::StringLiteral(_) => s,
+            }).collect(),
+        }),
+    }
+}
+
+pub fn fold_location_children<F: IrFolder + ?Sized>(f: &mut F, ir: &mut IRRoot, location: Location) -> Location {
+    Location {
+        var: location.var,
+        index: location.index.map(|e| f.fold_expr(ir, e)),
+    }
+}
+
+pub fn fold_expr_children<F: IrFolder + ?Sized>(f: &mut F, ir: &mut IRRoot, expr: ExprId) -> ExprId {
+    let folded = match ir[expr].expr.clone() {
+        ExprType::Location(l) => ExprType::Location(f.fold_location(ir, l)),
+        ExprType::MethodCall(m) => ExprType::MethodCall(fold_method_call(f, ir, m)),
+        ExprType::Unary(u) => ExprType::Unary(Unary { expr: f.fold_expr(ir, u.expr), op: u.op }),
+        ExprType::Binary(b) => {
+            let lhs = f.fold_expr(ir, b.lhs);
+            let rhs = f.fold_expr(ir, b.rhs);
+            ExprType::Binary(Binary { lhs, rhs, op: b.op })
+        }
+        ExprType::Literal(_) | ExprType::LenId(_) => return expr,
+    };
+    ir[expr].expr = folded;
+    expr
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::*;
+    use crate::compile;
+    use crate::semantic_analyzer::lint::LintConfig;
+
+    // records the source text of every literal and location, in visiting order
+    struct Order<'a> {
+        code: &'a str,
+        seen: Vec<&'a str>,
+    }
+
+    impl<'a> IrVisitor for Order<'a> {
+        fn visit_expr(&mut self, ir: &IRRoot, expr: ExprId) {
+            let e = &ir[expr];
+            if matches!(e.expr, ExprType::Literal(_) | ExprType::Location(_)) {
+                self.seen.push(&self.code[e.span.start..e.span.end]);
+            }
+            walk_expr(self, ir, expr);
+        }
+    }
+
+    #[test]
+    fn test_visitor_evaluation_order() {
+        let code = "int a[5];
+            void main() {
+                int i;
+                a[1] = 2 + 3;
+                for (i = 4; i < 5; i += 6) { if (a[7] > 8) { break; } else { a[0] = 9; } }
+                while (false) { continue; }
+            }";
+        let ir = compile(code, &LintConfig::default()).unwrap().ir;
+        let mut order = Order { code, seen: Vec::new() };
+        order.visit_program(&ir);
+        assert_eq!(order.seen, vec![
+            "1", "2", "3",
+            "4", "i", "5", "a[7]", "7", "8", "0", "9", "6",
+            "false",
+        ]);
+    }
+
+    // counts the declarations and uses of every variable
+    #[derive(Default)]
+    struct Vars {
+        decls: HashMap<VarId, usize>,
+        uses: HashMap<VarId, usize>,
+    }
+
+    impl IrVisitor for Vars {
+        fn visit_var_decl(&mut self, _ir: &IRRoot, var: VarId) {
+            *self.decls.entry(var).or_default() += 1;
+        }
+
+        fn visit_location(&mut self, ir: &IRRoot, location: &Location) {
+            *self.uses.entry(location.var).or_default() += 1;
+            walk_location(self, ir, location);
+        }
+    }
+
+    #[test]
+    fn test_visitor_loop_var_is_a_use() {
+        let code = "void main() { int i; for (i = 0; i < 3; i++) { } }";
+        let ir = compile(code, &LintConfig::default()).unwrap().ir;
+        let mut vars = Vars::default();
+        vars.visit_program(&ir);
+        let i = ir[ir.root.method_decls[0]].block.field_decls[0];
+        assert_eq!(vars.decls.values().sum::<usize>(), 1);
+        assert_eq!(vars.decls[&i], 1);
+        // the initializer, the condition and the update
+        assert_eq!(vars.uses[&i], 3);
+    }
+
+    // folds `<int> + <int>` and drops `while (false)` loops
+    struct Simplify;
+
+    impl IrFolder for Simplify {
+        fn fold_statement(&mut self, ir: &mut IRRoot, statement: StmtId) -> Vec<StmtId> {
+            let dead = match &ir[statement].stmt {
+                StmtType::While(w) => matches!(ir[w.cond].expr, ExprType::Literal(Literal::BoolLiteral(false))),
+                _ => false,
+            };
+            if dead {
+                Vec::new()
+            } else {
+                vec![fold_statement_children(self, ir, statement)]
+            }
+        }
+
+        fn fold_expr(&mut self, ir: &mut IRRoot, expr: ExprId) -> ExprId {
+            let expr = fold_expr_children(self, ir, expr);
+            let sum = match &ir[expr].expr {
+                ExprType::Binary(Binary { lhs, rhs, op: BinaryOp::Add }) => match (&ir[*lhs].expr, &ir[*rhs].expr) {
+                    (ExprType::Literal(Literal::IntLiteral(l)), ExprType::Literal(Literal::IntLiteral(r))) => Some(l + r),
+                    _ => None,
+                },
+                _ => None,
+            };
+            if let Some(sum) = sum {
+                ir[expr].expr = ExprType::Literal(Literal::IntLiteral(sum));
+            }
+            expr
+        }
+    }
+
+    #[test]
+    fn test_folder() {
+        let code = "void main() { int x; x = 1 + 2 + 3; while (false) { x = 1 + 1; } for (x = 0; x < 2 + 2; x++) { break; } }";
+        let mut ir = compile(code, &LintConfig::default()).unwrap().ir;
+        Simplify.fold_program(&mut ir);
+        let statements = &ir[ir.root.method_decls[0]].block.statements;
+        assert_eq!(statements.len(), 2);
+
+        let literal = |e: ExprId| match ir[e].expr {
+            ExprType::Literal(Literal::IntLiteral(i)) => i,
+            ref e => panic!("expected a literal, found {:?}", e),
+        };
+        match &ir[statements[0]].stmt {
+            StmtType::Assign(Assign { assign_expr: AssignExpr::AssignOpExpr(a), .. }) => assert_eq!(literal(a.expr), 6),
+            s => panic!("unexpected statement {:?}", s),
+        }
+        match &ir[statements[1]].stmt {
+            StmtType::For(f) => match &ir[f.cond].expr {
+                ExprType::Binary(b) => assert_eq!(literal(b.rhs), 4),
+                e => panic!("unexpected condition {:?}", e),
+            },
+            s => panic!("unexpected statement {:?}", s),
+        };
+    }
+}
@@ -0,0 +1,266 @@
+//! Style checks over the checked IR. Unlike `SemanticCheckError`s, lints
+//! never stop compilation (unless `--deny-warnings` is given), and each one
+//! can be turned on or off by name.
+
+use std::collections::{ HashMap, HashSet };
+use std::str::FromStr;
+
+use crate::ast::Span;
+
+use super::errors::SemanticCheckWarning;
+use super::ir::visit::{ self, IrVisitor };
+use super::ir::{ self, IRRoot, MethodId, VarId };
+
+/// A kind of warning, turned on with `-W <name>` and off with `-A <name>`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Lint {
+    UnusedVariable,
+    UnusedMethod,
+    Shadowing,
+    LoopVarAssign,
+    SelfAssign,
+    ConstantCondition,
+    UnreachableCode,
+    MissingReturn,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 8] = [
+        Lint::UnusedVariable,
+        Lint::UnusedMethod,
+        Lint::Shadowing,
+        Lint::LoopVarAssign,
+        Lint::SelfAssign,
+        Lint::ConstantCondition,
+        Lint::UnreachableCode,
+        Lint::MissingReturn,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused-variable",
+            Lint::UnusedMethod => "unused-method",
+            Lint::Shadowing => "shadowing",
+            Lint::LoopVarAssign => "loop-var-assign",
+            Lint::SelfAssign => "self-assign",
+            Lint::ConstantCondition => "constant-condition",
+            Lint::UnreachableCode => "unreachable-code",
+            Lint::MissingReturn => "missing-return",
+        }
+    }
+
+    // shadowing is legal and common in Decaf, so it is only checked on request
+    fn enabled_by_default(self) -> bool {
+        self != Lint::Shadowing
+    }
+}
+
+impl FromStr for Lint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Lint::ALL.iter().find(|l| l.name() == s).copied().ok_or_else(|| {
+            let names: Vec<&str> = Lint::ALL.iter().map(|l| l.name()).collect();
+            format!("unknown lint `{}`, expected one of: {}", s, names.join(", "))
+        })
+    }
+}
+
+/// Which lints are reported, and whether they fail compilation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintConfig {
+    enabled: HashSet<Lint>,
+    pub deny_warnings: bool,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            enabled: Lint::ALL.iter().copied().filter(|l| l.enabled_by_default()).collect(),
+            deny_warnings: false,
+        }
+    }
+}
+
+impl LintConfig {
+    pub fn warn(&mut self, lint: Lint) {
+        self.enabled.insert(lint);
+    }
+
+    pub fn allow(&mut self, lint: Lint) {
+        self.enabled.remove(&lint);
+    }
+
+    pub fn is_enabled(&self, lint: Lint) -> bool {
+        self.enabled.contains(&lint)
+    }
+}
+
+/// Runs every lint except the ones `check_flow` takes care of.
+pub fn check_lints(ir: &IRRoot) -> Vec<SemanticCheckWarning> {
+    let mut linter = Linter {
+        globals: HashMap::new(),
+        args: HashMap::new(),
+        method: None,
+        stmt_span: None,
+        loop_vars: Vec::new(),
+        read: HashSet::new(),
+        called: HashSet::new(),
+        warnings: Vec::new(),
+    };
+    for &var in ir.root.import_decls.iter().chain(&ir.root.field_decls) {
+        linter.globals.insert(ir[var].name.clone(), ir[var].span);
+    }
+    for &method in &ir.root.method_decls {
+        linter.globals.insert(ir[method].name.clone(), ir[method].span);
+    }
+
+    linter.visit_program(ir);
+
+    let mut warnings = linter.warnings;
+    for (i, var) in ir.vars.iter().enumerate() {
+        if var.scope != ir::VarScope::Import && !linter.read.contains(&VarId(i as u32)) {
+            warnings.push(SemanticCheckWarning::UnusedVariable { name: var.name.clone(), span: var.span });
+        }
+    }
+    for &method in &ir.root.method_decls {
+        if ir[method].name != "main" && !linter.called.contains(&method) {
+            warnings.push(SemanticCheckWarning::UnusedMethod { name: ir[method].name.clone(), span: ir[method].span });
+        }
+    }
+    warnings
+}
+
+struct Linter {
+    // imports, fields and methods by name
+    globals: HashMap<String, Span>,
+    // arguments of the current method by name
+    args: HashMap<String, Span>,
+    method: Option<MethodId>,
+    stmt_span: Option<Span>,
+    // variables of the `for` loops around the current statement
+    loop_vars: Vec<VarId>,
+    read: HashSet<VarId>,
+    // methods called from some other method
+    called: HashSet<MethodId>,
+    warnings: Vec<SemanticCheckWarning>,
+}
+
+impl Linter {
+    fn check_shadowing(&mut self, ir: &IRRoot, var: VarId, is_arg: bool) {
+        let decl = &ir[var];
+        let shadowed = if is_arg { None } else { self.args.get(&decl.name) };
+        if let Some(&shadowed) = shadowed.or_else(|| self.globals.get(&decl.name)) {
+            self.warnings.push(SemanticCheckWarning::Shadowing { name: decl.name.clone(), span: decl.span, shadowed });
+        }
+    }
+
+    fn check_condition(&mut self, ir: &IRRoot, cond: ir::ExprId, is_loop: bool) {
+        // `while (true)` is how an endless loop is written
+        let endless = matches!(ir[cond].expr, ir::ExprType::Literal(ir::Literal::BoolLiteral(true)));
+        if is_constant(ir, cond) && !(is_loop && endless) {
+            self.warnings.push(SemanticCheckWarning::ConstantCondition { span: ir[cond].span });
+        }
+    }
+}
+
+impl IrVisitor for Linter {
+    fn visit_method_decl(&mut self, ir: &IRRoot, method: MethodId) {
+        self.method = Some(method);
+        self.args.clear();
+        for &arg in &ir[method].args {
+            self.check_shadowing(ir, arg, true);
+            self.args.insert(ir[arg].name.clone(), ir[arg].span);
+        }
+        visit::walk_method_decl(self, ir, method);
+    }
+
+    fn visit_block(&mut self, ir: &IRRoot, block: &ir::Block) {
+        for &var in &block.field_decls {
+            self.check_shadowing(ir, var, false);
+        }
+        visit::walk_block(self, ir, block);
+    }
+
+    fn visit_statement(&mut self, ir: &IRRoot, statement: ir::StmtId) {
+        self.stmt_span = Some(ir[statement].span);
+        visit::walk_statement(self, ir, statement);
+    }
+
+    fn visit_assign(&mut self, ir: &IRRoot, assign: &ir::Assign) {
+        let name = &ir[assign.dst.var].name;
+        let span = self.stmt_span.unwrap();
+        if self.loop_vars.contains(&assign.dst.var) {
+            self.warnings.push(SemanticCheckWarning::LoopVarAssign { name: name.clone(), span });
+        }
+        if let ir::AssignExpr::AssignOpExpr(ir::AssignOpExpr { assign_op: ir::AssignOp::Assign, expr }) = &assign.assign_expr {
+            if let ir::ExprType::Location(src) = &ir[*expr].expr {
+                if same_location(ir, &assign.dst, src) {
+                    self.warnings.push(SemanticCheckWarning::SelfAssign { name: name.clone(), span });
+                }
+            }
+        }
+        visit::walk_assign(self, ir, assign);
+    }
+
+    fn visit_if_else(&mut self, ir: &IRRoot, if_else: &ir::IfElse) {
+        self.check_condition(ir, if_else.cond, false);
+        visit::walk_if_else(self, ir, if_else);
+    }
+
+    fn visit_for(&mut self, ir: &IRRoot, for_: &ir::For) {
+        self.check_condition(ir, for_.cond, true);
+        self.loop_vars.push(for_.var);
+        visit::walk_for(self, ir, for_);
+        self.loop_vars.pop();
+    }
+
+    fn visit_while(&mut self, ir: &IRRoot, while_: &ir::While) {
+        self.check_condition(ir, while_.cond, true);
+        visit::walk_while(self, ir, while_);
+    }
+
+    fn visit_expr(&mut self, ir: &IRRoot, expr: ir::ExprId) {
+        match &ir[expr].expr {
+            ir::ExprType::Location(l) => {
+                self.read.insert(l.var);
+            }
+            ir::ExprType::LenId(var) => {
+                self.read.insert(*var);
+            }
+            _ => {}
+        }
+        visit::walk_expr(self, ir, expr);
+    }
+
+    fn visit_method_call(&mut self, ir: &IRRoot, call: &ir::MethodCall) {
+        if let ir::MethodCall::Method(m) = call {
+            if Some(m.method) != self.method {
+                self.called.insert(m.method);
+            }
+        }
+        visit::walk_method_call(self, ir, call);
+    }
+}
+
+// whether `expr` is made of literals only
+fn is_constant(ir: &IRRoot, expr: ir::ExprId) -> bool {
+    match &ir[expr].expr {
+        ir::ExprType::Literal(_) => true,
+        ir::ExprType::Unary(u) => is_constant(ir, u.expr),
+        ir::ExprType::Binary(b) => is_constant(ir, b.lhs) && is_constant(ir, b.rhs),
+        ir::ExprType::Location(_) | ir::ExprType::LenId(_) | ir::ExprType::MethodCall(_) => false,
+    }
+}
+
+fn same_location(ir: &IRRoot, a: &ir::Location, b: &ir::Location) -> bool {
+    a.var == b.var && match (a.index, b.index) {
+        (None, None) => true,
+        (Some(a), Some(b)) => match (&ir[a].expr, &ir[b].expr) {
+            (ir::ExprType::Literal(a), ir::ExprType::Literal(b)) => a == b,
+            (ir::ExprType::Location(a), ir::ExprType::Location(b)) => same_location(ir, a, b),
+            _ => false,
+        },
+        _ => false,
+    }
+}
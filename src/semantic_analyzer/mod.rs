@@ -1,20 +1,26 @@
-mod ir;
+pub mod ir;
 mod env;
-mod errors;
+pub mod errors;
 mod pre_check;
 mod analyzer;
+mod flow;
+pub mod lint;
 pub use analyzer::SemanticAnalyzer;
+pub use flow::check_flow;
+pub use lint::check_lints;
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::parser::DecafParser;
+    use super::errors::SemanticCheckError;
+    use crate::ast::Span;
+    use crate::parser::parse;
     use crate::test_util::get_current_dir;
     use std::fs::read_to_string;
     use std::path::PathBuf;
 
     macro_rules! test_sa_illegal {
-        ( $testname:ident, $filename:expr ) => {
+        ( $testname:ident, $filename:expr, $error:ident ) => {
             #[test]
             fn $testname() {
                 let path = get_current_dir();
@@ -22,9 +28,10 @@ mod tests {
                     .iter()
                     .collect();
                 let s = read_to_string(&path).unwrap();
-                let program = DecafParser::new().parse(&s).unwrap();
-                let res = SemanticAnalyzer::new().create_ir(program);
-                assert!(res.is_err());
+                let program = parse(&s).unwrap();
+                let errors = SemanticAnalyzer::new().create_ir(program).unwrap_err();
+                assert_eq!(errors.len(), 1, "{:?}", errors);
+                assert!(matches!(errors[0], SemanticCheckError::$error { .. }), "{:?}", errors);
             }
         };
     }
@@ -38,29 +45,279 @@ mod tests {
                     .iter()
                     .collect();
                 let s = read_to_string(&path).unwrap();
-                let program = DecafParser::new().parse(&s).unwrap();
+                let program = parse(&s).unwrap();
                 let res = SemanticAnalyzer::new().create_ir(program);
                 assert!(res.is_ok());
             }
         };
     }
 
-    test_sa_illegal!(test_sa_illegal_01, "illegal-01.dcf");
-    test_sa_illegal!(test_sa_illegal_02, "illegal-02.dcf");
-    test_sa_illegal!(test_sa_illegal_03, "illegal-03.dcf");
-    test_sa_illegal!(test_sa_illegal_04, "illegal-04.dcf");
-    test_sa_illegal!(test_sa_illegal_05, "illegal-05.dcf");
-    test_sa_illegal!(test_sa_illegal_06, "illegal-06.dcf");
-    test_sa_illegal!(test_sa_illegal_07, "illegal-07.dcf");
-    test_sa_illegal!(test_sa_illegal_08, "illegal-08.dcf");
-    test_sa_illegal!(test_sa_illegal_09, "illegal-09.dcf");
-    test_sa_illegal!(test_sa_illegal_10, "illegal-10.dcf");
-    test_sa_illegal!(test_sa_illegal_11, "illegal-11.dcf");
-    test_sa_illegal!(test_sa_illegal_12, "illegal-12.dcf");
-    test_sa_illegal!(test_sa_illegal_13, "illegal-13.dcf");
-    test_sa_illegal!(test_sa_illegal_14, "illegal-14.dcf");
-    test_sa_illegal!(test_sa_illegal_15, "illegal-15.dcf");
-    test_sa_illegal!(test_sa_illegal_16, "illegal-16.dcf");
-    test_sa_illegal!(test_sa_illegal_17, "illegal-17.dcf");
+    #[test]
+    fn test_sa_error_payload() {
+        let code = "int foo(int a, bool b) { return a; }\nvoid main() { int x; x = foo(34, 35); }";
+        let program = parse(code).unwrap();
+        let errors = SemanticAnalyzer::new().create_ir(program).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        match &errors[0] {
+            SemanticCheckError::InvalidMethodArgs { name, span, expected, actual } => {
+                assert_eq!(name, "foo");
+                assert_eq!(&code[span.start..span.end], "35");
+                assert_eq!(*expected, ir::Type::Bool);
+                assert_eq!(*actual, ir::Type::Int);
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_sa_int_literal_range() {
+        let code = "int a[4294967296];\nvoid main() { int x; x = 0x80000000; x = -2147483649; }";
+        let program = parse(code).unwrap();
+        let errors = SemanticAnalyzer::new().create_ir(program).unwrap_err();
+        let literals: Vec<&str> = errors
+            .iter()
+            .map(|e| match e {
+                SemanticCheckError::IntLiteralOutOfRange { literal, .. } => literal.as_str(),
+                e => panic!("unexpected error {:?}", e),
+            })
+            .collect();
+        assert_eq!(literals, vec!["4294967296", "0x80000000", "-2147483649"]);
+    }
+
+    #[test]
+    fn test_sa_operators() {
+        let code = "void main() { int x; bool b; \
+            x = x + x; x = x - x; x = x * x; x = x / x; x = x % x; x = -x; \
+            b = x < x; b = x <= x; b = x > x; b = x >= x; b = x == x; b = x != x; \
+            b = b && b; b = b || b; b = !b; }";
+        let ir = SemanticAnalyzer::new().create_ir(parse(code).unwrap()).unwrap();
+        let method = &ir[ir.root.method_decls[0]];
+        let ops: Vec<String> = method.block.statements.iter().map(|&s| match &ir[s].stmt {
+            ir::StmtType::Assign(ir::Assign { assign_expr: ir::AssignExpr::AssignOpExpr(a), .. }) => match &ir[a.expr].expr {
+                ir::ExprType::Binary(b) => format!("{:?}", b.op),
+                ir::ExprType::Unary(u) => format!("{:?}", u.op),
+                e => panic!("unexpected expression {:?}", e),
+            },
+            s => panic!("unexpected statement {:?}", s),
+        }).collect();
+        assert_eq!(ops, vec![
+            "Add", "Sub", "Mul", "Div", "Mod", "NegInt",
+            "LT", "LE", "GT", "GE", "EQ", "NE",
+            "And", "Or", "NegBool",
+        ]);
+    }
+
+    #[test]
+    fn test_sa_ir_ids() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ir::IRRoot>();
+
+        let code = "int a[3];\nint f(int n) { if (n > 0) { return f(n - 1); } return a[n]; }\nvoid main() { f(2); }";
+        let ir = SemanticAnalyzer::new().create_ir(parse(code).unwrap()).unwrap();
+        let f = ir.root.method_decls[0];
+        assert_eq!(ir[f].name, "f");
+        let n = ir[f].args[0];
+        assert_eq!(ir[n].scope, ir::VarScope::Method(f));
+        assert_eq!(ir[ir.root.field_decls[0]].scope, ir::VarScope::Global);
+
+        // both returns and the recursive call refer to `f` itself
+        let mut returns = 0;
+        for stmt in &ir.stmts {
+            if let ir::StmtType::Return(r) = &stmt.stmt {
+                assert_eq!(r.method, f);
+                returns += 1;
+            }
+        }
+        assert_eq!(returns, 2);
+        assert!(ir.exprs.iter().any(|e| matches!(&e.expr, ir::ExprType::MethodCall(ir::MethodCall::Method(m)) if m.method == f)));
+
+        // the IR is plain data, so analyzing the same program twice gives equal trees
+        let again = SemanticAnalyzer::new().create_ir(parse(code).unwrap()).unwrap();
+        assert_eq!(ir, again);
+    }
+
+    #[test]
+    fn test_sa_loop_targets() {
+        let code = "void main() { int i; \
+            for (i = 0; i < 3; i++) { while (true) { if (i > 1) { break; } continue; } break; } \
+            while (true) { for (i = 0; i < 3; i++) { continue; } break; } }";
+        let ir = SemanticAnalyzer::new().create_ir(parse(code).unwrap()).unwrap();
+        let loop_of = |stmt: &ir::Statement| match stmt.stmt {
+            ir::StmtType::For(ref f) => f.loop_,
+            ir::StmtType::While(ref w) => w.loop_,
+            _ => unreachable!(),
+        };
+        let loops: Vec<(ir::LoopId, Span)> = ir.stmts
+            .iter()
+            .filter(|s| matches!(s.stmt, ir::StmtType::For(_) | ir::StmtType::While(_)))
+            .map(|s| (loop_of(s), s.span))
+            .collect();
+        assert_eq!(loops.len(), ir.loops.len());
+
+        // every `break`/`continue` targets the innermost loop around it
+        let mut jumps = 0;
+        for stmt in &ir.stmts {
+            let target = match stmt.stmt {
+                ir::StmtType::Break(l) | ir::StmtType::Continue(l) => l,
+                _ => continue,
+            };
+            let innermost = loops
+                .iter()
+                .filter(|(_, span)| span.start <= stmt.span.start && stmt.span.end <= span.end)
+                .min_by_key(|(_, span)| span.end - span.start)
+                .unwrap();
+            assert_eq!(target, innermost.0, "wrong target for `{}`", &code[stmt.span.start..stmt.span.end]);
+            assert_eq!(ir[target].span, innermost.1);
+            jumps += 1;
+        }
+        assert_eq!(jumps, 5);
+    }
+
+    #[test]
+    fn test_sa_break_outside_loop() {
+        // the loop in `foo` fails to check, which must not leave `main` inside it
+        let code = "void foo() { int i; for (i = 0; i < 3; i++) { i = true; } }\n\
+            void main() { if (true) { break; } continue; }";
+        let errors = SemanticAnalyzer::new().create_ir(parse(code).unwrap()).unwrap_err();
+        assert!(matches!(errors[0], SemanticCheckError::OperandsTypeMismatch { .. }));
+        assert!(errors.iter().any(|e| matches!(e, SemanticCheckError::InvalidBreak { .. })));
+        assert!(errors.iter().any(|e| matches!(e, SemanticCheckError::InvalidContinue { .. })));
+    }
+
+    #[test]
+    fn test_sa_recovery() {
+        // every independent mistake is reported once, and nothing that follows from them
+        let code = "int a[10];\n\
+            int foo(int x) { y = x; y = y + 1; return y; }\n\
+            void bar() { if (a) { a[true] = 1; } foo(a); foo(); }\n\
+            void main() { int z; z = foo(1) + bar(); z += len(z); }";
+        let errors = SemanticAnalyzer::new().create_ir(parse(code).unwrap()).unwrap_err();
+        let names: Vec<_> = errors.iter().map(|e| format!("{:?}", e)).map(|e| e.split(' ').next().unwrap().to_string()).collect();
+        assert_eq!(names, [
+            "UsedBeforeDeclared",
+            "LocationTypeMismatch",
+            "TypeOfExprMustInt",
+            "ArrayAsMethodArg",
+            "MethodSignatureMismatch",
+            "MethodMustReturnValue",
+            "IdMustArray",
+        ], "{:?}", errors);
+    }
+
+    #[test]
+    fn test_sa_flow() {
+        use super::errors::SemanticCheckWarning;
+
+        let code = "int both(bool b) { if (b) { return 1; } else { return 0; } }\n\
+            int forever() { while (true) { return 1; } }\n\
+            int some(int x) { if (x > 0) { return 1; } }\n\
+            int broken() { while (true) { break; } }\n\
+            int dead(bool b) { if (false) { return 1; b = false; } if (true) { return 0; } else { return 2; b = true; } }\n\
+            int spin() { while (true) { if (false) { break; } } }\n\
+            void main() { int i; for (i = 0; i < 3; i++) { continue; i = 1; } return; main(); }";
+        let mut ir = SemanticAnalyzer::new().create_ir(parse(code).unwrap()).unwrap();
+        let warnings = check_flow(&mut ir);
+
+        let falls_off: Vec<&str> = ir.methods.iter().filter(|m| m.falls_off).map(|m| m.name.as_str()).collect();
+        assert_eq!(falls_off, vec!["some", "broken"]);
+
+        let unreachable: Vec<(&str, &str)> = warnings
+            .iter()
+            .filter_map(|w| match w {
+                SemanticCheckWarning::UnreachableCode { span, cause } => Some((&code[span.start..span.end], &code[cause.start..cause.end])),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(unreachable, vec![
+            ("b = false;", "return 1;"),
+            ("b = true;", "return 2;"),
+            ("i = 1;", "continue;"),
+            ("main();", "return;"),
+        ]);
+        assert_eq!(warnings.len(), 6);
+    }
+
+    #[test]
+    fn test_sa_lints() {
+        use super::errors::SemanticCheckWarning;
+        use super::lint::Lint;
+
+        let code = "int g;\n\
+            int helper(int n) { return helper(n - 1); }\n\
+            void main() { int i, g, a[4]; \
+            for (i = 0; i < len(a); i++) { i = 0; a[i] = a[i]; } \
+            if (1 < 2) { while (true) { break; } } }";
+        let ir = SemanticAnalyzer::new().create_ir(parse(code).unwrap()).unwrap();
+        let lints: Vec<(Lint, &str)> = check_lints(&ir)
+            .iter()
+            .map(|w| {
+                let span = match w {
+                    SemanticCheckWarning::UnusedVariable { span, .. }
+                    | SemanticCheckWarning::UnusedMethod { span, .. }
+                    | SemanticCheckWarning::Shadowing { span, .. }
+                    | SemanticCheckWarning::LoopVarAssign { span, .. }
+                    | SemanticCheckWarning::SelfAssign { span, .. }
+                    | SemanticCheckWarning::ConstantCondition { span } => span,
+                    w => panic!("unexpected warning {:?}", w),
+                };
+                (w.lint(), &code[span.start..span.end])
+            })
+            .collect();
+        assert_eq!(lints, vec![
+            (Lint::Shadowing, "g"),
+            (Lint::LoopVarAssign, "i = 0;"),
+            (Lint::SelfAssign, "a[i] = a[i];"),
+            (Lint::ConstantCondition, "1 < 2"),
+            (Lint::UnusedVariable, "g"),
+            (Lint::UnusedVariable, "g"),
+            (Lint::UnusedMethod, "helper"),
+        ]);
+    }
+
+    test_sa_illegal!(test_sa_illegal_01, "illegal-01.dcf", DuplicatedVar);
+    test_sa_illegal!(test_sa_illegal_02, "illegal-02.dcf", UsedBeforeDeclared);
+    test_sa_illegal!(test_sa_illegal_03, "illegal-03.dcf", MainMethodShouldOnlyOne);
+    test_sa_illegal!(test_sa_illegal_04, "illegal-04.dcf", ArrayLenShouldPositive);
+    test_sa_illegal!(test_sa_illegal_05, "illegal-05.dcf", MethodSignatureMismatch);
+    test_sa_illegal!(test_sa_illegal_06, "illegal-06.dcf", InvalidMethodArgs);
+    test_sa_illegal!(test_sa_illegal_07, "illegal-07.dcf", UnexpectedReturnValue);
+    test_sa_illegal!(test_sa_illegal_08, "illegal-08.dcf", ReturnTypeMismatch);
+    test_sa_illegal!(test_sa_illegal_09, "illegal-09.dcf", TypeOfExprMustInt);
+    test_sa_illegal!(test_sa_illegal_10, "illegal-10.dcf", LocationTypeMismatch);
+    test_sa_illegal!(test_sa_illegal_11, "illegal-11.dcf", ConditionTypeShouldBool);
+    test_sa_illegal!(test_sa_illegal_12, "illegal-12.dcf", OperandsTypeMismatch);
+    test_sa_illegal!(test_sa_illegal_13, "illegal-13.dcf", OperandsTypeMismatch);
+    test_sa_illegal!(test_sa_illegal_14, "illegal-14.dcf", OperandsTypeMismatch);
+    test_sa_illegal!(test_sa_illegal_15, "illegal-15.dcf", OperandsTypeMismatch);
+    test_sa_illegal!(test_sa_illegal_16, "illegal-16.dcf", OperandsTypeMismatch);
+    test_sa_illegal!(test_sa_illegal_17, "illegal-17.dcf", OperandsTypeMismatch);
+    test_sa_illegal!(test_sa_illegal_18, "illegal-18.dcf", IntLiteralOutOfRange);
+    test_sa_illegal!(test_sa_illegal_19, "illegal-19.dcf", DuplicatedMethod);
+    test_sa_illegal!(test_sa_illegal_20, "illegal-20.dcf", DuplicatedVar);
+    test_sa_illegal!(test_sa_illegal_21, "illegal-21.dcf", UsedBeforeDeclared);
+    test_sa_illegal!(test_sa_illegal_22, "illegal-22.dcf", MainMethodArgsShouldEmpty);
+    test_sa_illegal!(test_sa_illegal_23, "illegal-23.dcf", MainMethodShouldReturnVoid);
+    test_sa_illegal!(test_sa_illegal_24, "illegal-24.dcf", MethodMustReturnValue);
+    test_sa_illegal!(test_sa_illegal_25, "illegal-25.dcf", ArrayAsMethodArg);
+    test_sa_illegal!(test_sa_illegal_26, "illegal-26.dcf", NotAVariable);
+    test_sa_illegal!(test_sa_illegal_27, "illegal-27.dcf", NotAMethod);
+    test_sa_illegal!(test_sa_illegal_28, "illegal-28.dcf", LocationTypeMismatch);
+    test_sa_illegal!(test_sa_illegal_29, "illegal-29.dcf", IdMustArray);
+    test_sa_illegal!(test_sa_illegal_30, "illegal-30.dcf", ConditionTypeShouldBool);
+    test_sa_illegal!(test_sa_illegal_31, "illegal-31.dcf", ConditionTypeShouldBool);
+    test_sa_illegal!(test_sa_illegal_32, "illegal-32.dcf", InvalidBreak);
+    test_sa_illegal!(test_sa_illegal_33, "illegal-33.dcf", InvalidContinue);
+    test_sa_illegal!(test_sa_illegal_34, "illegal-34.dcf", InvalidLoopVar);
+    test_sa_illegal!(test_sa_illegal_35, "illegal-35.dcf", InvalidLoopVar);
+    test_sa_illegal!(test_sa_illegal_36, "illegal-36.dcf", ReturnTypeMismatch);
+    test_sa_illegal!(test_sa_illegal_37, "illegal-37.dcf", OperandsTypeMismatch);
+    test_sa_illegal!(test_sa_illegal_38, "illegal-38.dcf", OperandsTypeMismatch);
+    test_sa_illegal!(test_sa_illegal_39, "illegal-39.dcf", OperandsTypeMismatch);
+    test_sa_illegal!(test_sa_illegal_40, "illegal-40.dcf", LocationTypeMismatch);
     test_sa_legal!(test_sa_legal_01, "legal-01.dcf");
+    test_sa_legal!(test_sa_legal_02, "legal-02.dcf");
+    test_sa_legal!(test_sa_legal_03, "legal-03.dcf");
+    test_sa_legal!(test_sa_legal_04, "legal-04.dcf");
+    test_sa_legal!(test_sa_legal_05, "legal-05.dcf");
+    test_sa_legal!(test_sa_legal_06, "legal-06.dcf");
 }
@@ -3,17 +3,21 @@ use crate::ast;
 use super::errors::{ SemanticCheckError };
 
 pub fn check_main(p: &ast::Program) -> Result<(), SemanticCheckError> {
-    let methods: Vec<&ast::MethodDecl> = p.method_decls.iter().filter(|m| m.id == "main").collect();
+    let methods: Vec<&ast::MethodDecl> = p.method_decls.iter().filter(|m| m.id.node == "main").collect();
     if methods.len() != 1 {
-        return Err(SemanticCheckError::MainMethodShouldOnlyOne);
+        return Err(SemanticCheckError::MainMethodShouldOnlyOne {
+            spans: methods.iter().map(|m| m.id.span).collect(),
+        });
     }
 
     if !methods[0].args.is_empty() {
-        return Err(SemanticCheckError::MainMethodArgsShouldEmpty);
+        return Err(SemanticCheckError::MainMethodArgsShouldEmpty {
+            span: methods[0].args[0].span.to(methods[0].args.last().unwrap().span),
+        });
     }
 
     if methods[0].return_type != ast::ReturnType::Void {
-        return Err(SemanticCheckError::MainMethodShouldReturnVoid);
+        return Err(SemanticCheckError::MainMethodShouldReturnVoid { span: methods[0].id.span });
     }
 
     Ok(())
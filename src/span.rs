@@ -0,0 +1,112 @@
+use std::ops::Deref;
+
+/// Half-open range of byte offsets `[start, end)` into the source text.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(&self, other: Span) -> Self {
+        Self {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+}
+
+/// An AST node together with the source range it was parsed from.
+#[derive(Debug, Clone)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Self {
+        Self { node, span }
+    }
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.node
+    }
+}
+
+/// 1-based line and column of a byte offset. Columns count characters.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LineCol {
+    pub line: usize,
+    pub col: usize,
+}
+
+/// Maps byte offsets of a source text to lines and columns.
+pub struct LineIndex<'a> {
+    src: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(src: &'a str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(src.match_indices('\n').map(|(i, _)| i + 1));
+        Self { src, line_starts }
+    }
+
+    pub fn line_col(&self, offset: usize) -> LineCol {
+        let offset = offset.min(self.src.len());
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(l) => l,
+            Err(l) => l - 1,
+        };
+        let col = self.src[self.line_starts[line]..offset].chars().count() + 1;
+        LineCol { line: line + 1, col }
+    }
+
+    /// The text of the 1-based line `line`, without its line terminator.
+    pub fn line(&self, line: usize) -> &'a str {
+        let start = self.line_starts[line - 1];
+        let end = match self.line_starts.get(line) {
+            Some(next) => next - 1,
+            None => self.src.len(),
+        };
+        self.src[start..end].trim_end_matches('\r')
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_col() {
+        let index = LineIndex::new("int a;\nvoid main() {\n}");
+        assert_eq!(index.line_col(0), LineCol { line: 1, col: 1 });
+        assert_eq!(index.line_col(4), LineCol { line: 1, col: 5 });
+        assert_eq!(index.line_col(6), LineCol { line: 1, col: 7 });
+        assert_eq!(index.line_col(7), LineCol { line: 2, col: 1 });
+        assert_eq!(index.line_col(12), LineCol { line: 2, col: 6 });
+        assert_eq!(index.line_col(21), LineCol { line: 3, col: 1 });
+        assert_eq!(index.line(2), "void main() {");
+        assert_eq!(index.line(3), "}");
+        assert_eq!(index.line_count(), 3);
+    }
+
+    #[test]
+    fn test_span_to() {
+        assert_eq!(Span::new(3, 5).to(Span::new(8, 10)), Span::new(3, 10));
+    }
+}
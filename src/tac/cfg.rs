@@ -0,0 +1,336 @@
+//! Control-flow graphs of three-address code, the base for dataflow
+//! analysis.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::ops::{ Index, IndexMut };
+
+use super::*;
+
+id_type!(BlockId);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub label: Label,
+    /// straight-line code ending in a `Jump`, `Branch` or `Return`; empty
+    /// for the entry and exit blocks
+    pub instrs: Vec<Instr>,
+    pub succs: Vec<BlockId>,
+    pub preds: Vec<BlockId>,
+}
+
+/// The basic blocks of a method.
+///
+/// The entry block leads to the first block of the method, and every
+/// `return` leads to the exit block. Blocks that can't be reached are left
+/// out, and critical edges (from a block with several successors to one with
+/// several predecessors) are split by an extra block, so that code can
+/// always be placed on an edge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cfg {
+    pub blocks: Vec<BasicBlock>,
+    pub entry: BlockId,
+    pub exit: BlockId,
+    /// the number of labels used, including the ones made for new blocks
+    pub labels: u32,
+}
+
+impl Index<BlockId> for Cfg {
+    type Output = BasicBlock;
+
+    fn index(&self, id: BlockId) -> &BasicBlock {
+        &self.blocks[id.index()]
+    }
+}
+
+impl IndexMut<BlockId> for Cfg {
+    fn index_mut(&mut self, id: BlockId) -> &mut BasicBlock {
+        &mut self.blocks[id.index()]
+    }
+}
+
+impl Cfg {
+    pub fn new(method: &Method) -> Self {
+        let mut labels = method.labels;
+        let mut fresh = || {
+            labels += 1;
+            Label(labels - 1)
+        };
+
+        // split at labels and after jumps; labels in a row name one block
+        let mut raw: Vec<(Vec<Label>, Vec<Instr>)> = vec![(Vec::new(), Vec::new())];
+        for instr in &method.body {
+            let (names, instrs) = raw.last_mut().unwrap();
+            match instr {
+                Instr::Label(l) if instrs.is_empty() => names.push(*l),
+                Instr::Label(l) => raw.push((vec![*l], Vec::new())),
+                _ if instrs.last().is_some_and(is_terminator) => raw.push((Vec::new(), vec![instr.clone()])),
+                _ => instrs.push(instr.clone()),
+            }
+        }
+
+        let mut blocks: Vec<BasicBlock> = raw
+            .iter()
+            .map(|(names, _)| BasicBlock {
+                label: names.first().copied().unwrap_or_else(&mut fresh),
+                instrs: Vec::new(),
+                succs: Vec::new(),
+                preds: Vec::new(),
+            })
+            .collect();
+        let mut alias = HashMap::new();
+        for (i, (names, _)) in raw.iter().enumerate() {
+            for l in names {
+                alias.insert(*l, blocks[i].label);
+            }
+        }
+
+        // every block ends in a jump, so that blocks can be reordered
+        for (i, (_, instrs)) in raw.into_iter().enumerate() {
+            let mut instrs: Vec<Instr> = instrs.into_iter().map(|instr| retarget(instr, |l| alias[&l])).collect();
+            if !instrs.last().is_some_and(is_terminator) {
+                instrs.push(match blocks.get(i + 1) {
+                    Some(next) => Instr::Jump(next.label),
+                    None => Instr::Return(None),
+                });
+            }
+            blocks[i].instrs = instrs;
+        }
+
+        // keep the blocks reachable from the first one, in their order
+        let by_label: HashMap<Label, usize> = blocks.iter().enumerate().map(|(i, b)| (b.label, i)).collect();
+        let mut reachable = vec![false; blocks.len()];
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            if !reachable[i] {
+                reachable[i] = true;
+                stack.extend(targets(blocks[i].instrs.last().unwrap()).iter().map(|l| by_label[l]));
+            }
+        }
+        let mut kept = reachable.iter();
+        blocks.retain(|_| *kept.next().unwrap());
+
+        let entry = BasicBlock { label: fresh(), instrs: Vec::new(), succs: Vec::new(), preds: Vec::new() };
+        let exit = BasicBlock { label: fresh(), instrs: Vec::new(), succs: Vec::new(), preds: Vec::new() };
+        blocks.insert(0, entry);
+        blocks.push(exit);
+        let mut cfg = Cfg {
+            entry: BlockId(0),
+            exit: BlockId(blocks.len() as u32 - 1),
+            blocks,
+            labels: 0,
+        };
+
+        let by_label: HashMap<Label, BlockId> = cfg.blocks.iter().enumerate().map(|(i, b)| (b.label, BlockId(i as u32))).collect();
+        cfg.add_edge(cfg.entry, BlockId(1));
+        for i in 1..cfg.exit.index() {
+            let from = BlockId(i as u32);
+            match cfg[from].instrs.last().unwrap() {
+                Instr::Return(_) => cfg.add_edge(from, cfg.exit),
+                last => {
+                    for l in targets(last) {
+                        cfg.add_edge(from, by_label[&l]);
+                    }
+                }
+            }
+        }
+
+        cfg.split_critical_edges(&mut fresh);
+        cfg.labels = labels;
+        cfg
+    }
+
+    fn add_edge(&mut self, from: BlockId, to: BlockId) {
+        if !self[from].succs.contains(&to) {
+            self[from].succs.push(to);
+            self[to].preds.push(from);
+        }
+    }
+
+    fn split_critical_edges(&mut self, fresh: &mut impl FnMut() -> Label) {
+        for i in 0..self.blocks.len() {
+            let from = BlockId(i as u32);
+            if self[from].succs.len() < 2 {
+                continue;
+            }
+            for to in self[from].succs.clone() {
+                if self[to].preds.len() < 2 {
+                    continue;
+                }
+                let mid = BlockId(self.blocks.len() as u32);
+                let label = fresh();
+                self.blocks.push(BasicBlock {
+                    label,
+                    instrs: vec![Instr::Jump(self[to].label)],
+                    succs: vec![to],
+                    preds: vec![from],
+                });
+
+                let to_label = self[to].label;
+                let last = self[from].instrs.pop().unwrap();
+                self[from].instrs.push(retarget(last, |l| if l == to_label { label } else { l }));
+                replace(&mut self[from].succs, to, mid);
+                replace(&mut self[to].preds, from, mid);
+            }
+        }
+    }
+
+    /// Renders the graph as a Graphviz cluster named after `method`, for
+    /// `to_dot`.
+    fn write_dot(&self, out: &mut String, names: &Names) {
+        let method = &names.method.name;
+        let node = |b: BlockId| format!("\"{}.{}\"", method, b.0);
+
+        writeln!(out, "    subgraph \"cluster_{}\" {{", method).unwrap();
+        writeln!(out, "        label=\"{}\";", method).unwrap();
+        for (i, block) in self.blocks.iter().enumerate() {
+            let id = BlockId(i as u32);
+            let text = if id == self.entry {
+                "entry".to_string()
+            } else if id == self.exit {
+                "exit".to_string()
+            } else {
+                let mut text = format!("{}:\\l", block.label);
+                for instr in &block.instrs {
+                    text.push_str(&escape_dot(&names.instr(instr)));
+                    text.push_str("\\l");
+                }
+                text
+            };
+            writeln!(out, "        {} [label=\"{}\"];", node(id), text).unwrap();
+        }
+        for (i, block) in self.blocks.iter().enumerate() {
+            for &succ in &block.succs {
+                let label = match block.instrs.last() {
+                    Some(Instr::Branch { if_true, if_false, .. }) if if_true != if_false => {
+                        if *if_true == self[succ].label { " [label=\"true\"]" } else { " [label=\"false\"]" }
+                    }
+                    _ => "",
+                };
+                writeln!(out, "        {} -> {}{};", node(BlockId(i as u32)), node(succ), label).unwrap();
+            }
+        }
+        writeln!(out, "    }}").unwrap();
+    }
+}
+
+/// Renders the control-flow graphs of every method of `program` as a
+/// Graphviz graph.
+pub fn to_dot(program: &Program) -> String {
+    let mut out = String::new();
+    writeln!(out, "digraph cfg {{").unwrap();
+    writeln!(out, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+    for method in &program.methods {
+        Cfg::new(method).write_dot(&mut out, &Names { program, method });
+    }
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+fn is_terminator(instr: &Instr) -> bool {
+    matches!(instr, Instr::Jump(_) | Instr::Branch { .. } | Instr::Return(_))
+}
+
+fn targets(instr: &Instr) -> Vec<Label> {
+    match instr {
+        Instr::Jump(l) => vec![*l],
+        Instr::Branch { if_true, if_false, .. } => vec![*if_true, *if_false],
+        _ => Vec::new(),
+    }
+}
+
+fn retarget(instr: Instr, f: impl Fn(Label) -> Label) -> Instr {
+    match instr {
+        Instr::Jump(l) => Instr::Jump(f(l)),
+        Instr::Branch { cond, if_true, if_false } => Instr::Branch { cond, if_true: f(if_true), if_false: f(if_false) },
+        instr => instr,
+    }
+}
+
+fn replace(ids: &mut [BlockId], old: BlockId, new: BlockId) {
+    for id in ids.iter_mut().filter(|id| **id == old) {
+        *id = new;
+    }
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile;
+    use crate::semantic_analyzer::lint::LintConfig;
+
+    fn lower_code(code: &str) -> Program {
+        lower(&compile(code, &LintConfig::default()).unwrap().ir)
+    }
+
+    #[test]
+    fn test_cfg_edges() {
+        let program = lower_code("int f(int x) { \
+            while (x > 0) { if (x == 3 || x == 5) { return x; } x -= 1; continue; x = 7; } \
+            return 0; }\n\
+            void main() { f(9); }");
+        let cfg = Cfg::new(&program.methods[0]);
+
+        assert!(cfg[cfg.entry].preds.is_empty());
+        assert_eq!(cfg[cfg.entry].succs, vec![BlockId(1)]);
+        assert!(cfg[cfg.exit].succs.is_empty());
+        assert_eq!(cfg[cfg.exit].preds.len(), 2);
+
+        let by_label: HashMap<Label, BlockId> = cfg.blocks.iter().enumerate().map(|(i, b)| (b.label, BlockId(i as u32))).collect();
+        for (i, block) in cfg.blocks.iter().enumerate() {
+            let id = BlockId(i as u32);
+            for &succ in &block.succs {
+                assert!(cfg[succ].preds.contains(&id));
+                assert!(block.succs.len() < 2 || cfg[succ].preds.len() < 2, "critical edge {:?} -> {:?}", id, succ);
+            }
+            for &pred in &block.preds {
+                assert!(cfg[pred].succs.contains(&id));
+            }
+            if id == cfg.entry || id == cfg.exit {
+                continue;
+            }
+
+            // the jump at the end of each block agrees with its edges
+            let (last, rest) = block.instrs.split_last().unwrap();
+            assert!(rest.iter().all(|i| !is_terminator(i) && !matches!(i, Instr::Label(_))));
+            let succs: Vec<BlockId> = match last {
+                Instr::Return(_) => vec![cfg.exit],
+                _ => targets(last).iter().map(|l| by_label[l]).collect(),
+            };
+            assert_eq!(succs, block.succs);
+        }
+
+        // `x = 7` comes after `continue` and is dropped
+        let copies = cfg.blocks.iter().flat_map(|b| &b.instrs).filter(|i| matches!(i, Instr::Copy { src: Operand::Const(7), .. }));
+        assert_eq!(copies.count(), 0);
+    }
+
+    #[test]
+    fn test_cfg_dot() {
+        let program = lower_code("void main() { int x; if (x > 0) { x = 1; } }");
+        let expected = r#"digraph cfg {
+    node [shape=box, fontname="monospace"];
+    subgraph "cluster_main" {
+        label="main";
+        "main.0" [label="entry"];
+        "main.1" [label="L2:\lx = 0\l%1 = x > 0\lif %1 goto L0 else L5\l"];
+        "main.2" [label="L0:\lx = 1\lgoto L1\l"];
+        "main.3" [label="L1:\lreturn\l"];
+        "main.4" [label="exit"];
+        "main.5" [label="L5:\lgoto L1\l"];
+        "main.0" -> "main.1";
+        "main.1" -> "main.2" [label="true"];
+        "main.1" -> "main.5" [label="false"];
+        "main.2" -> "main.3";
+        "main.3" -> "main.4";
+        "main.5" -> "main.3";
+    }
+}
+"#;
+        assert_eq!(to_dot(&program), expected);
+    }
+}
@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+
+use crate::semantic_analyzer::ir::{ self, IRRoot, LoopId, MethodId, VarId };
+
+use super::*;
+
+/// Lowers a checked program to three-address code.
+pub fn lower(ir: &IRRoot) -> Program {
+    let mut program = Program::default();
+    let mut globals = HashMap::new();
+    for &var in &ir.root.field_decls {
+        globals.insert(var, Var::Global(GlobalId(program.globals.len() as u32)));
+        program.globals.push(Global {
+            name: ir[var].name.clone(),
+            len: ir[var].arr_len.map(|len| len as u32),
+        });
+    }
+
+    for &method in &ir.root.method_decls {
+        let lowerer = Lowerer {
+            ir,
+            globals: &globals,
+            strings: &mut program.strings,
+            locals: HashMap::new(),
+            loops: HashMap::new(),
+            method: Method {
+                name: ir[method].name.clone(),
+                returns_value: ir[method].return_type != ir::ReturnType::Void,
+                params: ir[method].args.len(),
+                locals: Vec::new(),
+                body: Vec::new(),
+                labels: 0,
+            },
+        };
+        program.methods.push(lowerer.lower_method(method));
+    }
+    program
+}
+
+struct Lowerer<'a> {
+    ir: &'a IRRoot,
+    globals: &'a HashMap<VarId, Var>,
+    strings: &'a mut Vec<String>,
+    locals: HashMap<VarId, Var>,
+    // where `continue` and `break` jump to
+    loops: HashMap<LoopId, (Label, Label)>,
+    method: Method,
+}
+
+impl Lowerer<'_> {
+    fn lower_method(mut self, method: MethodId) -> Method {
+        let ir = self.ir;
+        for &arg in &ir[method].args {
+            self.declare(arg);
+        }
+        self.lower_block(&ir[method].block);
+        if !matches!(self.method.body.last(), Some(Instr::Return(_) | Instr::Jump(_))) {
+            self.emit(Instr::Return(None));
+        }
+        self.method
+    }
+
+    fn emit(&mut self, instr: Instr) {
+        self.method.body.push(instr);
+    }
+
+    fn label(&mut self) -> Label {
+        self.method.labels += 1;
+        Label(self.method.labels - 1)
+    }
+
+    fn temp(&mut self) -> Var {
+        self.method.locals.push(Local { name: None, len: None });
+        Var::Local(LocalId(self.method.locals.len() as u32 - 1))
+    }
+
+    fn declare(&mut self, var: VarId) -> Var {
+        let decl = &self.ir[var];
+        // locals in nested scopes may reuse a name, which gets a suffix
+        let taken = |name: &str| self.method.locals.iter().any(|l| l.name.as_deref() == Some(name));
+        let mut name = decl.name.clone();
+        let mut n = 1;
+        while taken(&name) {
+            name = format!("{}.{}", decl.name, n);
+            n += 1;
+        }
+
+        self.method.locals.push(Local { name: Some(name), len: decl.arr_len.map(|len| len as u32) });
+        let local = Var::Local(LocalId(self.method.locals.len() as u32 - 1));
+        self.locals.insert(var, local);
+        local
+    }
+
+    fn var(&self, var: VarId) -> Var {
+        self.locals.get(&var).or_else(|| self.globals.get(&var)).copied().unwrap()
+    }
+
+    fn lower_block(&mut self, block: &ir::Block) {
+        // locals start out as zero every time their scope is entered
+        for &var in &block.field_decls {
+            let local = self.declare(var);
+            match self.ir[var].arr_len {
+                Some(len) => self.zero_array(local, len),
+                None => self.emit(Instr::Copy { dst: local, src: Operand::Const(0) }),
+            }
+        }
+        for &stmt in &block.statements {
+            self.lower_statement(stmt);
+        }
+    }
+
+    fn zero_array(&mut self, array: Var, len: i32) {
+        let (i, cond) = (self.temp(), self.temp());
+        let (head, body, end) = (self.label(), self.label(), self.label());
+        self.emit(Instr::Copy { dst: i, src: Operand::Const(0) });
+        self.emit(Instr::Label(head));
+        self.emit(Instr::Binary { dst: cond, op: BinaryOp::Lt, lhs: Operand::Var(i), rhs: Operand::Const(len) });
+        self.emit(Instr::Branch { cond: Operand::Var(cond), if_true: body, if_false: end });
+        self.emit(Instr::Label(body));
+        self.emit(Instr::Store { array, index: Operand::Var(i), src: Operand::Const(0) });
+        self.emit(Instr::Binary { dst: i, op: BinaryOp::Add, lhs: Operand::Var(i), rhs: Operand::Const(1) });
+        self.emit(Instr::Jump(head));
+        self.emit(Instr::Label(end));
+    }
+
+    fn lower_statement(&mut self, stmt: ir::StmtId) {
+        let ir = self.ir;
+        match &ir[stmt].stmt {
+            ir::StmtType::Assign(assign) => {
+                let (op, val) = match &assign.assign_expr {
+                    ir::AssignExpr::AssignOpExpr(a) => {
+                        let op = match &a.assign_op {
+                            ir::AssignOp::Assign => None,
+                            ir::AssignOp::CompoundAssignOp(op) => Some(compound_op(op)),
+                        };
+                        (op, Some(a.expr))
+                    }
+                    ir::AssignExpr::Increment(inc) => (Some(increment_op(inc)), None),
+                };
+                self.lower_update(&assign.dst, op, val);
+            }
+            ir::StmtType::MethodCall(call) => {
+                self.lower_call(call, None);
+            }
+            ir::StmtType::IfElse(if_else) => {
+                let (then, end) = (self.label(), self.label());
+                let otherwise = if if_else.else_block.is_some() { self.label() } else { end };
+                self.lower_cond(if_else.cond, then, otherwise);
+                self.emit(Instr::Label(then));
+                self.lower_block(&if_else.if_block);
+                if let Some(block) = &if_else.else_block {
+                    self.emit(Instr::Jump(end));
+                    self.emit(Instr::Label(otherwise));
+                    self.lower_block(block);
+                }
+                self.emit(Instr::Label(end));
+            }
+            ir::StmtType::For(for_) => {
+                let init = self.lower_expr(for_.init_expr);
+                let var = self.var(for_.var);
+                self.emit(Instr::Copy { dst: var, src: init });
+
+                let (head, body, update, end) = (self.label(), self.label(), self.label(), self.label());
+                self.loops.insert(for_.loop_, (update, end));
+                self.emit(Instr::Label(head));
+                self.lower_cond(for_.cond, body, end);
+                self.emit(Instr::Label(body));
+                self.lower_block(&for_.block);
+                self.emit(Instr::Label(update));
+                match &for_.update.update_expr {
+                    ir::ForUpdateExpr::AssignExpr(a) => {
+                        self.lower_update(&for_.update.dst, Some(compound_op(&a.compound_assign_op)), Some(a.expr))
+                    }
+                    ir::ForUpdateExpr::Increment(inc) => self.lower_update(&for_.update.dst, Some(increment_op(inc)), None),
+                }
+                self.emit(Instr::Jump(head));
+                self.emit(Instr::Label(end));
+            }
+            ir::StmtType::While(while_) => {
+                let (head, body, end) = (self.label(), self.label(), self.label());
+                self.loops.insert(while_.loop_, (head, end));
+                self.emit(Instr::Label(head));
+                self.lower_cond(while_.cond, body, end);
+                self.emit(Instr::Label(body));
+                self.lower_block(&while_.block);
+                self.emit(Instr::Jump(head));
+                self.emit(Instr::Label(end));
+            }
+            ir::StmtType::Return(ret) => {
+                let val = ret.val.map(|v| self.lower_expr(v));
+                self.emit(Instr::Return(val));
+            }
+            ir::StmtType::Break(loop_) => self.emit(Instr::Jump(self.loops[loop_].1)),
+            ir::StmtType::Continue(loop_) => self.emit(Instr::Jump(self.loops[loop_].0)),
+        }
+    }
+
+    // `dst = val`, `dst op= val` or, without a value, `dst op= 1`; the index
+    // of `dst` is evaluated once, before the value
+    fn lower_update(&mut self, dst: &ir::Location, op: Option<BinaryOp>, val: Option<ir::ExprId>) {
+        let var = self.var(dst.var);
+        let index = dst.index.map(|i| self.lower_expr(i));
+        let val = match val {
+            Some(v) => self.lower_expr(v),
+            None => Operand::Const(1),
+        };
+
+        let val = match op {
+            None => val,
+            Some(op) => {
+                let old = match index {
+                    Some(index) => {
+                        let old = self.temp();
+                        self.emit(Instr::Load { dst: old, array: var, index });
+                        Operand::Var(old)
+                    }
+                    None => Operand::Var(var),
+                };
+                let new = self.temp();
+                self.emit(Instr::Binary { dst: new, op, lhs: old, rhs: val });
+                Operand::Var(new)
+            }
+        };
+
+        match index {
+            Some(index) => self.emit(Instr::Store { array: var, index, src: val }),
+            None => self.emit(Instr::Copy { dst: var, src: val }),
+        }
+    }
+
+    // jumps to `if_true` or `if_false` depending on the value of `cond`
+    fn lower_cond(&mut self, cond: ir::ExprId, if_true: Label, if_false: Label) {
+        let ir = self.ir;
+        match &ir[cond].expr {
+            ir::ExprType::Literal(ir::Literal::BoolLiteral(b)) => self.emit(Instr::Jump(if *b { if_true } else { if_false })),
+            ir::ExprType::Unary(ir::Unary { expr, op: ir::UnaryOp::NegBool }) => self.lower_cond(*expr, if_false, if_true),
+            ir::ExprType::Binary(ir::Binary { lhs, rhs, op: ir::BinaryOp::And }) => {
+                let rhs_label = self.label();
+                self.lower_cond(*lhs, rhs_label, if_false);
+                self.emit(Instr::Label(rhs_label));
+                self.lower_cond(*rhs, if_true, if_false);
+            }
+            ir::ExprType::Binary(ir::Binary { lhs, rhs, op: ir::BinaryOp::Or }) => {
+                let rhs_label = self.label();
+                self.lower_cond(*lhs, if_true, rhs_label);
+                self.emit(Instr::Label(rhs_label));
+                self.lower_cond(*rhs, if_true, if_false);
+            }
+            _ => {
+                let cond = self.lower_expr(cond);
+                self.emit(Instr::Branch { cond, if_true, if_false });
+            }
+        }
+    }
+
+    fn lower_expr(&mut self, expr: ir::ExprId) -> Operand {
+        let ir = self.ir;
+        match &ir[expr].expr {
+            ir::ExprType::Literal(l) => Operand::Const(match l {
+                ir::Literal::IntLiteral(i) => *i,
+                ir::Literal::CharLiteral(c) => *c as i32,
+                ir::Literal::BoolLiteral(b) => *b as i32,
+            }),
+            ir::ExprType::LenId(var) => Operand::Const(ir[*var].arr_len.unwrap()),
+            ir::ExprType::Location(l) => {
+                let var = self.var(l.var);
+                match (l.index, var) {
+                    (Some(index), _) => {
+                        let index = self.lower_expr(index);
+                        let dst = self.temp();
+                        self.emit(Instr::Load { dst, array: var, index });
+                        Operand::Var(dst)
+                    }
+                    // a call later in the same expression may change a global,
+                    // so its value is taken now
+                    (None, Var::Global(_)) => {
+                        let dst = self.temp();
+                        self.emit(Instr::Copy { dst, src: Operand::Var(var) });
+                        Operand::Var(dst)
+                    }
+                    (None, Var::Local(_)) => Operand::Var(var),
+                }
+            }
+            ir::ExprType::MethodCall(call) => {
+                let dst = self.temp();
+                self.lower_call(call, Some(dst));
+                Operand::Var(dst)
+            }
+            ir::ExprType::Unary(u) => {
+                let src = self.lower_expr(u.expr);
+                let op = match u.op {
+                    ir::UnaryOp::NegInt => UnaryOp::Neg,
+                    ir::UnaryOp::NegBool => UnaryOp::Not,
+                };
+                let dst = self.temp();
+                self.emit(Instr::Unary { dst, op, src });
+                Operand::Var(dst)
+            }
+            ir::ExprType::Binary(b) if matches!(b.op, ir::BinaryOp::And | ir::BinaryOp::Or) => {
+                // `dst = lhs`, and only if that doesn't decide it, `dst = rhs`
+                let dst = self.temp();
+                let (rhs_label, end) = (self.label(), self.label());
+                let lhs = self.lower_expr(b.lhs);
+                self.emit(Instr::Copy { dst, src: lhs });
+                let (if_true, if_false) = match b.op {
+                    ir::BinaryOp::And => (rhs_label, end),
+                    _ => (end, rhs_label),
+                };
+                self.emit(Instr::Branch { cond: Operand::Var(dst), if_true, if_false });
+                self.emit(Instr::Label(rhs_label));
+                let rhs = self.lower_expr(b.rhs);
+                self.emit(Instr::Copy { dst, src: rhs });
+                self.emit(Instr::Label(end));
+                Operand::Var(dst)
+            }
+            ir::ExprType::Binary(b) => {
+                let lhs = self.lower_expr(b.lhs);
+                let rhs = self.lower_expr(b.rhs);
+                let dst = self.temp();
+                self.emit(Instr::Binary { dst, op: binary_op(&b.op), lhs, rhs });
+                Operand::Var(dst)
+            }
+        }
+    }
+
+    fn lower_call(&mut self, call: &ir::MethodCall, dst: Option<Var>) {
+        let (callee, args) = match call {
+            ir::MethodCall::Method(m) => {
+                let args = m.args.iter().map(|&a| Arg::Operand(self.lower_expr(a))).collect();
+                (Callee::Method(self.ir[m.method].name.clone()), args)
+            }
+            ir::MethodCall::Callout(c) => {
+                let args = c.args
+                    .iter()
+                    .map(|a| match a {
+                        ir::ImportArg::Expr(e) => Arg::Operand(self.lower_expr(*e)),
+                        ir::ImportArg::StringLiteral(s) => Arg::String(self.string(s)),
+                    })
+                    .collect();
+                (Callee::Extern(c.name.clone()), args)
+            }
+        };
+        self.emit(Instr::Call { dst, callee, args });
+    }
+
+    fn string(&mut self, s: &str) -> StringId {
+        let id = match self.strings.iter().position(|t| t == s) {
+            Some(i) => i,
+            None => {
+                self.strings.push(s.to_string());
+                self.strings.len() - 1
+            }
+        };
+        StringId(id as u32)
+    }
+}
+
+fn binary_op(op: &ir::BinaryOp) -> BinaryOp {
+    match op {
+        ir::BinaryOp::Add => BinaryOp::Add,
+        ir::BinaryOp::Sub => BinaryOp::Sub,
+        ir::BinaryOp::Mul => BinaryOp::Mul,
+        ir::BinaryOp::Div => BinaryOp::Div,
+        ir::BinaryOp::Mod => BinaryOp::Mod,
+        ir::BinaryOp::LT => BinaryOp::Lt,
+        ir::BinaryOp::LE => BinaryOp::Le,
+        ir::BinaryOp::GT => BinaryOp::Gt,
+        ir::BinaryOp::GE => BinaryOp::Ge,
+        ir::BinaryOp::EQ => BinaryOp::Eq,
+        ir::BinaryOp::NE => BinaryOp::Ne,
+        ir::BinaryOp::And | ir::BinaryOp::Or => unreachable!("`&&` and `||` are lowered to jumps"),
+    }
+}
+
+fn compound_op(op: &ir::CompoundAssignOp) -> BinaryOp {
+    match op {
+        ir::CompoundAssignOp::AddAssign => BinaryOp::Add,
+        ir::CompoundAssignOp::SubAssign => BinaryOp::Sub,
+    }
+}
+
+fn increment_op(inc: &ir::Increment) -> BinaryOp {
+    match inc {
+        ir::Increment::SelfAdd => BinaryOp::Add,
+        ir::Increment::SelfSub => BinaryOp::Sub,
+    }
+}
@@ -0,0 +1,285 @@
+//! Three-address code: a linear, low-level form of the checked IR.
+//!
+//! Every instruction applies at most one operator, to constants and
+//! variables; temporaries hold intermediate results, control flow is made
+//! of labels and explicit jumps, and `&&`/`||` are lowered to jumps so that
+//! the right-hand side is only evaluated when it is needed.
+//!
+//! `bool`s are the integers `0` and `1`.
+
+use std::fmt;
+
+use crate::scanner::escape_string;
+use crate::semantic_analyzer::ir::id_type;
+
+pub mod cfg;
+mod lower;
+pub use lower::lower;
+
+id_type!(GlobalId);
+id_type!(LocalId);
+id_type!(StringId);
+id_type!(Label);
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Program {
+    pub globals: Vec<Global>,
+    /// string literals passed to callouts and imports
+    pub strings: Vec<String>,
+    pub methods: Vec<Method>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Global {
+    pub name: String,
+    /// the number of elements of an array
+    pub len: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Method {
+    pub name: String,
+    pub returns_value: bool,
+    /// the first `params` locals are the arguments, in order
+    pub params: usize,
+    pub locals: Vec<Local>,
+    pub body: Vec<Instr>,
+    /// the number of labels used, so that passes can make fresh ones
+    pub labels: u32,
+}
+
+/// A variable of a method, or a temporary if it has no name. Names are made
+/// unique within the method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Local {
+    pub name: Option<String>,
+    pub len: Option<u32>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Var {
+    Global(GlobalId),
+    Local(LocalId),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Operand {
+    Const(i32),
+    Var(Var),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instr {
+    /// `dst = src`
+    Copy { dst: Var, src: Operand },
+    /// `dst = op src`
+    Unary { dst: Var, op: UnaryOp, src: Operand },
+    /// `dst = lhs op rhs`
+    Binary { dst: Var, op: BinaryOp, lhs: Operand, rhs: Operand },
+    /// `dst = array[index]`
+    Load { dst: Var, array: Var, index: Operand },
+    /// `array[index] = src`
+    Store { array: Var, index: Operand, src: Operand },
+    Label(Label),
+    Jump(Label),
+    /// jumps to `if_true` if `cond` is not `0`, and to `if_false` otherwise
+    Branch { cond: Operand, if_true: Label, if_false: Label },
+    Call { dst: Option<Var>, callee: Callee, args: Vec<Arg> },
+    /// without a value in a method that returns one, control fell off the
+    /// end of the method, which is a run-time error
+    Return(Option<Operand>),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Callee {
+    Method(String),
+    /// an import or callout, called with the C calling convention
+    Extern(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Arg {
+    Operand(Operand),
+    String(StringId),
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self {
+            UnaryOp::Neg => "-",
+            UnaryOp::Not => "!",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "%",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "L{}", self.0)
+    }
+}
+
+/// Prints variables of `method`: globals as `@name`, locals by name and
+/// temporaries as `%n`.
+pub struct Names<'a> {
+    pub program: &'a Program,
+    pub method: &'a Method,
+}
+
+impl Names<'_> {
+    pub fn var(&self, var: Var) -> String {
+        match var {
+            Var::Global(g) => format!("@{}", self.program.globals[g.index()].name),
+            Var::Local(l) => match &self.method.locals[l.index()].name {
+                Some(name) => name.clone(),
+                None => format!("%{}", l.0),
+            },
+        }
+    }
+
+    pub fn operand(&self, operand: Operand) -> String {
+        match operand {
+            Operand::Const(c) => c.to_string(),
+            Operand::Var(v) => self.var(v),
+        }
+    }
+
+    pub fn instr(&self, instr: &Instr) -> String {
+        match instr {
+            Instr::Copy { dst, src } => format!("{} = {}", self.var(*dst), self.operand(*src)),
+            Instr::Unary { dst, op, src } => format!("{} = {}{}", self.var(*dst), op, self.operand(*src)),
+            Instr::Binary { dst, op, lhs, rhs } => {
+                format!("{} = {} {} {}", self.var(*dst), self.operand(*lhs), op, self.operand(*rhs))
+            }
+            Instr::Load { dst, array, index } => format!("{} = {}[{}]", self.var(*dst), self.var(*array), self.operand(*index)),
+            Instr::Store { array, index, src } => format!("{}[{}] = {}", self.var(*array), self.operand(*index), self.operand(*src)),
+            Instr::Label(l) => format!("{}:", l),
+            Instr::Jump(l) => format!("goto {}", l),
+            Instr::Branch { cond, if_true, if_false } => format!("if {} goto {} else {}", self.operand(*cond), if_true, if_false),
+            Instr::Call { dst, callee, args } => {
+                let args: Vec<String> = args
+                    .iter()
+                    .map(|a| match a {
+                        Arg::Operand(o) => self.operand(*o),
+                        Arg::String(s) => format!("\"{}\"", escape_string(&self.program.strings[s.index()])),
+                    })
+                    .collect();
+                let callee = match callee {
+                    Callee::Method(name) => name.clone(),
+                    Callee::Extern(name) => format!("extern {}", name),
+                };
+                let call = format!("call {}({})", callee, args.join(", "));
+                match dst {
+                    Some(dst) => format!("{} = {}", self.var(*dst), call),
+                    None => call,
+                }
+            }
+            Instr::Return(Some(val)) => format!("return {}", self.operand(*val)),
+            Instr::Return(None) => "return".to_string(),
+        }
+    }
+}
+
+fn with_len(name: &str, len: Option<u32>) -> String {
+    match len {
+        Some(len) => format!("{}[{}]", name, len),
+        None => name.to_string(),
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for global in &self.globals {
+            writeln!(f, "global {}", with_len(&format!("@{}", global.name), global.len))?;
+        }
+
+        for (i, method) in self.methods.iter().enumerate() {
+            if i > 0 || !self.globals.is_empty() {
+                writeln!(f)?;
+            }
+            let names = Names { program: self, method };
+            let params: Vec<String> = (0..method.params).map(|i| names.var(Var::Local(LocalId(i as u32)))).collect();
+            let return_type = if method.returns_value { "int" } else { "void" };
+            writeln!(f, "{} {}({}):", return_type, method.name, params.join(", "))?;
+            for local in method.locals.iter().skip(method.params) {
+                if let Some(name) = &local.name {
+                    writeln!(f, "    local {}", with_len(name, local.len))?;
+                }
+            }
+            for instr in &method.body {
+                match instr {
+                    Instr::Label(_) => writeln!(f, "{}", names.instr(instr))?,
+                    _ => writeln!(f, "    {}", names.instr(instr))?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile;
+    use crate::semantic_analyzer::lint::LintConfig;
+    use crate::test_util::get_current_dir;
+    use std::fs::read_to_string;
+    use std::path::PathBuf;
+
+    macro_rules! test_tac {
+        ( $testname:ident, $filename:expr ) => {
+            #[test]
+            fn $testname() {
+                let path = get_current_dir();
+                let dir: PathBuf = [&path, "src", "tac", "testcases"].iter().collect();
+                let s = read_to_string(dir.join($filename)).unwrap();
+                let expected = read_to_string(dir.join(concat!($filename, ".out"))).unwrap();
+                let ir = compile(&s, &LintConfig::default()).unwrap().ir;
+                assert_eq!(lower(&ir).to_string(), expected);
+            }
+        };
+    }
+
+    test_tac!(test_tac_short_circuit, "short-circuit.dcf");
+    test_tac!(test_tac_statements, "statements.dcf");
+}